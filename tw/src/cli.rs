@@ -16,6 +16,10 @@ pub mod commands {
             #[arg(short, long, value_name = "FILE_PATH")]
             path: Option<String>,
 
+            /// Path to the Lox file to execute, same as `--path`
+            #[arg(value_name = "FILE", conflicts_with = "path")]
+            file: Option<String>,
+
             /// Controlled debug mode
             #[arg(short, long)]
             debug: bool,
@@ -27,6 +31,22 @@ pub mod commands {
             /// Display the generated tokens
             #[arg(long)]
             show_tokens: bool,
+
+            /// Directory where imports are looked up, can be repeated
+            #[arg(short = 'I', long, value_name = "DIR")]
+            include: Vec<String>,
+
+            /// Lets the script read files and standard input
+            #[arg(long)]
+            allow_read: bool,
+
+            /// Lets the script write files
+            #[arg(long)]
+            allow_write: bool,
+
+            /// Arguments for the script, after `--`
+            #[arg(last = true, value_name = "ARGS")]
+            args: Vec<String>,
         },
 
         /// Development helper tools
@@ -44,6 +64,20 @@ pub mod commands {
             #[arg(value_name = "output_path")]
             output_path: String,
         },
+
+        /// Runs a file or every file in a directory on both backends and reports output differences
+        Conform {
+            /// Path to a Lox file or a directory with Lox files
+            #[arg(value_name = "PATH")]
+            path: String,
+        },
+
+        /// Executes a file with the bytecode VM
+        RunVm {
+            /// Path to the Lox file to execute
+            #[arg(value_name = "FILE_PATH")]
+            path: String,
+        },
     }
 }
 
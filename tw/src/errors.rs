@@ -1,17 +1,35 @@
 use std::fmt;
 use thiserror::Error;
 
-use crate::cli::alerts::Alert;
+use crate::{
+    cli::alerts::Alert,
+    lox::ast::{Arity, LiteralExpr},
+};
 
 #[derive(Debug)]
 pub struct Located<T> {
     pub error: T,
     pub line: usize,
+    /// File the error comes from, set once the error leaves the module that raised it
+    pub file: Option<String>,
+}
+
+impl<T> Located<T> {
+    pub fn new(error: T, line: usize) -> Self {
+        Self {
+            error,
+            line,
+            file: None,
+        }
+    }
 }
 
 impl<T: fmt::Display> fmt::Display for Located<T> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "[line {}] {}", self.line, self.error)
+        match &self.file {
+            Some(file) => write!(f, "[line {} in {}] {}", self.line, file, self.error),
+            None => write!(f, "[line {}] {}", self.line, self.error),
+        }
     }
 }
 
@@ -25,6 +43,9 @@ pub enum LoxError {
     Runtime(Located<RuntimeError>),
     #[error("SYS | {0}")]
     Io(#[from] IoError),
+    /// Not a failure, the script called `exit(code)`
+    #[error("EXIT | Script exited with code {0}")]
+    Exit(i32),
 }
 
 impl LoxError {
@@ -36,6 +57,38 @@ impl LoxError {
         self.report();
         std::process::exit(code);
     }
+
+    /// Sets the line of errors raised without one (line 0), like the ones from natives
+    pub fn or_at(mut self, line: usize) -> Self {
+        let located_line = match &mut self {
+            LoxError::Scan(located) => &mut located.line,
+            LoxError::Parse(located) => &mut located.line,
+            LoxError::Runtime(located) => &mut located.line,
+            LoxError::Io(_) | LoxError::Exit(_) => return self,
+        };
+
+        if *located_line == 0 {
+            *located_line = line;
+        }
+
+        self
+    }
+
+    /// Names the file the error comes from, keeping the innermost one when modules are nested
+    pub fn in_file(mut self, path: &str) -> Self {
+        let file = match &mut self {
+            LoxError::Scan(located) => &mut located.file,
+            LoxError::Parse(located) => &mut located.file,
+            LoxError::Runtime(located) => &mut located.file,
+            LoxError::Io(_) | LoxError::Exit(_) => return self,
+        };
+
+        if file.is_none() {
+            *file = Some(path.to_string());
+        }
+
+        self
+    }
 }
 
 pub trait LocateResult<T, E> {
@@ -44,19 +97,19 @@ pub trait LocateResult<T, E> {
 
 impl<T> LocateResult<T, ScanError> for Result<T, ScanError> {
     fn at(self, line: usize) -> Result<T, LoxError> {
-        self.map_err(|e| LoxError::Scan(Located { error: e, line }))
+        self.map_err(|e| LoxError::Scan(Located::new(e, line)))
     }
 }
 
 impl<T> LocateResult<T, ParseError> for Result<T, ParseError> {
     fn at(self, line: usize) -> Result<T, LoxError> {
-        self.map_err(|e| LoxError::Parse(Located { error: e, line }))
+        self.map_err(|e| LoxError::Parse(Located::new(e, line)))
     }
 }
 
 impl<T> LocateResult<T, RuntimeError> for Result<T, RuntimeError> {
     fn at(self, line: usize) -> Result<T, LoxError> {
-        self.map_err(|e| LoxError::Runtime(Located { error: e, line }))
+        self.map_err(|e| LoxError::Runtime(Located::new(e, line)))
     }
 }
 
@@ -66,6 +119,12 @@ pub enum ScanError {
     UnexpectedChar(char),
     #[error("Unterminated string.")]
     UnterminatedString,
+    #[error("Invalid escape sequence '\\{0}'.")]
+    InvalidEscape(char),
+    #[error("Invalid unicode escape, expected '\\u{{...}}' with 1 to 6 hex digits.")]
+    InvalidUnicodeEscape,
+    #[error("Invalid number literal '{0}'.")]
+    InvalidNumber(String),
 }
 
 #[derive(Error, Debug, PartialEq)]
@@ -86,12 +145,24 @@ pub enum ParseError {
     OutsideThis,
     #[error("Can't return a value from an initializer.")]
     ReturnInAnInitializer,
+    #[error("Can't yield from top-level code.")]
+    TopLevelYield,
+    #[error("Can't yield from an initializer.")]
+    YieldInAnInitializer,
+    #[error("Can't yield inside 'finally'.")]
+    YieldInFinally,
     #[error("A class can't inherit from itself.")]
     ClassInheritFromItself,
     #[error("Can't use 'super' outside of a class.")]
     OutsideSuper,
     #[error("Can't use 'super' in a class with no superclass")]
     SuperWithNoSuperclass,
+    #[error("Can't use 'this' in a class method.")]
+    StaticThis,
+    #[error("Can't use 'super' in a class method.")]
+    StaticSuper,
+    #[error("Can't use \"{0}\" as a module name, import its names with 'import {{ ... }} from'.")]
+    InvalidModuleName(String),
 }
 
 #[derive(Error, Debug, PartialEq)]
@@ -102,6 +173,10 @@ pub enum RuntimeError {
     InvalidBinaryOperands,
     #[error("Division by zero.")]
     DivisionByZero,
+    #[error("Operands must be integers.")]
+    IntegerExpected,
+    #[error("Shift amount must be between 0 and 63.")]
+    InvalidShift,
     #[error("Undefined variable \"{0}\".")]
     UndefinedVariable(String),
     #[error("Undefined property \"{0}\".")]
@@ -111,11 +186,66 @@ pub enum RuntimeError {
     #[error("Can only call functions and classes.")]
     NotCallable,
     #[error("Expected {0} arguments, but got {1}.")]
-    ArgumentCountMismatch(usize, usize),
+    ArgumentCountMismatch(Arity, usize),
     #[error("Only instances have properties.")]
     NotAnInstance,
     #[error("Superclass must be a class")]
     SuperclassMustBeAClass,
+    #[error("Module \"{0}\" not found.")]
+    ModuleNotFound(String),
+    #[error("Import cycle: {0}.")]
+    ImportCycle(String),
+    #[error("Module \"{0}\" has no global named \"{1}\".")]
+    UndefinedExport(String, String),
+    #[error("Only lists and maps can be indexed.")]
+    NotIndexable,
+    #[error("List index must be an integer.")]
+    InvalidIndex,
+    #[error("Index {0} out of range for a list of length {1}.")]
+    IndexOutOfRange(f64, usize),
+    #[error("Can't pop from an empty list.")]
+    EmptyList,
+    #[error("Map keys must be strings, numbers, booleans or nil.")]
+    InvalidMapKey,
+    #[error("Undefined key {0}.")]
+    UndefinedKey(String),
+    /// Value of a `throw`, `text` is how it prints when nothing catches it
+    #[error("Uncaught exception: {text}")]
+    Thrown { value: LiteralExpr, text: String },
+    #[error("Generator is already running.")]
+    GeneratorRunning,
+    #[error(
+        "Can only iterate over strings, lists, maps, generators and instances with an iterator() method."
+    )]
+    NotIterable,
+    #[error("Range step can't be zero.")]
+    ZeroStep,
+    #[error("Argument must be an instance.")]
+    InstanceExpected,
+    #[error("Argument must be a class.")]
+    ClassExpected,
+    #[error("Argument must be a string.")]
+    StringExpected,
+    #[error("{0}() must return a string.")]
+    NotAString(String),
+    #[error("Argument must be a list.")]
+    ListExpected,
+    #[error("Argument must be a single character.")]
+    CharacterExpected,
+    #[error("Can't convert \"{0}\" to a number.")]
+    InvalidNumber(String),
+    #[error("{0} is not a valid character code.")]
+    InvalidCharCode(f64),
+    #[error("Lower bound {0} is greater than upper bound {1}.")]
+    InvalidBounds(f64, f64),
+    #[error("{0}() needs the {1} permission.")]
+    PermissionDenied(String, String),
+    #[error("Can't {action} \"{path}\": {message}.")]
+    FileError {
+        action: String,
+        path: String,
+        message: String,
+    },
 }
 
 #[derive(Error, Debug)]
@@ -138,18 +268,18 @@ pub trait Locate {
 
 impl Locate for ScanError {
     fn at(self, line: usize) -> LoxError {
-        LoxError::Scan(Located { error: self, line })
+        LoxError::Scan(Located::new(self, line))
     }
 }
 
 impl Locate for ParseError {
     fn at(self, line: usize) -> LoxError {
-        LoxError::Parse(Located { error: self, line })
+        LoxError::Parse(Located::new(self, line))
     }
 }
 
 impl Locate for RuntimeError {
     fn at(self, line: usize) -> LoxError {
-        LoxError::Runtime(Located { error: self, line })
+        LoxError::Runtime(Located::new(self, line))
     }
 }
@@ -0,0 +1,10 @@
+pub mod cli;
+pub mod errors;
+pub mod lox;
+pub mod tools;
+
+pub use errors::LoxError;
+pub use lox::{
+    Capture, Interpreter, Lox, NativeModule, Value,
+    ast::{Arity, NativeFn},
+};
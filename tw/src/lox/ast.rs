@@ -1,13 +1,14 @@
 use std::collections::HashMap;
 use std::fmt;
+use std::rc::Rc;
 
 use crate::{
     errors::LoxError,
-    lox::{env::EnvId, interpreter::Interpreter},
+    lox::{env::EnvId, generator::Generator, interpreter::Interpreter, map::LoxMap},
     tools::AstPrinter,
 };
 
-use super::token::Token;
+use super::token::{Token, TokenType};
 
 // region: Macros
 
@@ -54,7 +55,7 @@ macro_rules! impl_new {
 #[derive(PartialEq, Debug, Clone)]
 pub enum Stmt {
     Expression(Expr),
-    Print(Expr),
+    Print(PrintStmt),
     Var(VarStmt),
     If(IfStmt),
     While(WhileStmt),
@@ -62,6 +63,10 @@ pub enum Stmt {
     Block(Vec<Stmt>),
     Return(ReturnStmt),
     Class(ClassStmt),
+    Import(ImportStmt),
+    Throw(ThrowStmt),
+    Try(TryStmt),
+    Yield(YieldStmt),
 }
 
 #[derive(PartialEq, Debug, Clone)]
@@ -78,6 +83,13 @@ pub enum Expr {
     Get(GetExpr),
     Set(SetExpr),
     Super(SuperExpr),
+    List(ListExpr),
+    Map(MapExpr),
+    Index(IndexExpr),
+    SetIndex(SetIndexExpr),
+    Stringify(StringifyExpr),
+    Iter(IterExpr),
+    Lambda(LambdaExpr),
 }
 
 #[derive(PartialEq, Debug, Clone)]
@@ -91,6 +103,9 @@ pub enum Callable {
 pub enum Object {
     Callable(Callable),
     Instance(ClassInstance),
+    List(Vec<LiteralExpr>),
+    Map(LoxMap),
+    Generator(Generator),
 }
 
 // endregion: AST Enums
@@ -105,6 +120,38 @@ pub struct ReturnStmt {
     pub value: Expr,
 }
 
+#[derive(Debug, PartialEq, Clone)]
+pub struct YieldStmt {
+    pub keyword: Token,
+    pub value: Expr,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct PrintStmt {
+    pub keyword: Token,
+    pub value: Expr,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ThrowStmt {
+    pub keyword: Token,
+    pub value: Expr,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct TryStmt {
+    pub body: Box<Stmt>,
+    pub catch: Option<CatchClause>,
+    pub finally: Option<Box<Stmt>>,
+}
+
+/// `catch (name) { ... }`, `name` holds the thrown value
+#[derive(Debug, PartialEq, Clone)]
+pub struct CatchClause {
+    pub name: Token,
+    pub body: Box<Stmt>,
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct IfStmt {
     pub condition: Expr,
@@ -129,6 +176,22 @@ pub struct ClassStmt {
     pub name: Token,
     pub superclass: Option<VarExpr>,
     pub methods: Vec<FunStmt>,
+    /// Methods declared with `class name() { ... }`, called on the class itself
+    pub class_methods: Vec<FunStmt>,
+    /// Methods declared without a parameter list, run when the property is read
+    pub getters: Vec<FunStmt>,
+    /// Fields declared with `class var name = value;`
+    pub fields: Vec<VarStmt>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ImportStmt {
+    pub keyword: Token,
+    pub path: String,
+    /// Names bound by `import { a, b } from "..."`
+    pub names: Vec<Token>,
+    /// Name bound to the whole module by `import "..."`, taken from the file name
+    pub namespace: Option<Token>,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -136,21 +199,40 @@ pub struct ClassDec {
     pub name: String,
     pub superclass: Option<Box<ClassDec>>,
     pub methods: HashMap<String, FunStmt>,
+    pub class_methods: HashMap<String, FunStmt>,
+    pub getters: HashMap<String, FunStmt>,
+    /// Static fields, only the copy stored on the heap is kept up to date
+    pub fields: HashMap<String, LiteralExpr>,
+    /// Heap id of the class object, copies of the declaration still point to it.
+    /// `None` for the classes of namespace objects, they are never on the heap
+    pub id: Option<usize>,
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct FunStmt {
     pub is_init: bool,
+    /// Set by the resolver when the body has a `yield`, calling it returns a generator
+    pub is_generator: bool,
     pub name: Token,
     pub params: Vec<Token>,
     pub body: Box<Stmt>,
     pub closure: Option<EnvId>,
 }
 
-#[derive(Debug, Clone)]
+pub type NativeAction = dyn Fn(&mut Interpreter, Vec<LiteralExpr>) -> Result<LiteralExpr, LoxError>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Arity {
+    Exact(usize),
+    Range(usize, usize),
+    Variadic,
+}
+
+#[derive(Clone)]
 pub struct NativeFn {
-    pub arity: u8,
-    pub action: fn(&mut Interpreter, Vec<LiteralExpr>) -> Result<LiteralExpr, LoxError>,
+    pub name: String,
+    pub arity: Arity,
+    pub action: Rc<NativeAction>,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -158,6 +240,8 @@ pub struct ClassInstance {
     pub id: usize,
     pub dec: ClassDec,
     pub fields: HashMap<String, LiteralExpr>,
+    /// Root node of the module this object is the namespace of, its globals are the fields
+    pub module: Option<EnvId>,
 }
 
 // endregion: Statements
@@ -190,6 +274,53 @@ pub struct GetExpr {
     pub name: Token,
 }
 
+#[derive(Debug, PartialEq, Clone)]
+pub struct ListExpr {
+    pub bracket: Token,
+    pub elements: Vec<Expr>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct MapExpr {
+    pub brace: Token,
+    pub entries: Vec<(Expr, Expr)>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct IndexExpr {
+    pub object: Box<Expr>,
+    pub bracket: Token,
+    pub index: Box<Expr>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct SetIndexExpr {
+    pub object: Box<Expr>,
+    pub bracket: Token,
+    pub index: Box<Expr>,
+    pub value: Box<Expr>,
+}
+
+/// Anonymous function, `fun (a) { ... }` or `(a) => ...`
+#[derive(Debug, PartialEq, Clone)]
+pub struct LambdaExpr {
+    pub function: FunStmt,
+}
+
+/// Converts a value to the string `print` shows, interpolated strings are built with it
+#[derive(Debug, PartialEq, Clone)]
+pub struct StringifyExpr {
+    pub quote: Token,
+    pub expression: Box<Expr>,
+}
+
+/// Iterator over the value of `expression`, the sequence of a `for-in` loop is wrapped in it
+#[derive(Debug, PartialEq, Clone)]
+pub struct IterExpr {
+    pub keyword: Token,
+    pub expression: Box<Expr>,
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct AssignmentExpr {
     pub name: Token,
@@ -237,6 +368,9 @@ pub enum LiteralExpr {
     String(String),
     Call(usize),
     Instance(usize),
+    Generator(usize),
+    List(usize),
+    Map(usize),
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -252,6 +386,10 @@ pub struct UnaryExpr {
 // region: Traits: Into
 impl_into!(Stmt;
     ClassStmt => Stmt::Class,
+    ImportStmt => Stmt::Import,
+    ThrowStmt => Stmt::Throw,
+    YieldStmt => Stmt::Yield,
+    TryStmt => Stmt::Try,
     ReturnStmt => Stmt::Return,
     FunStmt => Stmt::Function,
     IfStmt => Stmt::If,
@@ -272,7 +410,14 @@ impl_into!(Expr;
     GroupingExpr => Expr::Grouping,
     UnaryExpr => Expr::Unary,
     LiteralExpr => Expr::Literal,
-    SuperExpr => Expr::Super
+    SuperExpr => Expr::Super,
+    ListExpr => Expr::List,
+    MapExpr => Expr::Map,
+    IndexExpr => Expr::Index,
+    SetIndexExpr => Expr::SetIndex,
+    StringifyExpr => Expr::Stringify,
+    IterExpr => Expr::Iter,
+    LambdaExpr => Expr::Lambda
 );
 
 impl Into<Stmt> for LiteralExpr {
@@ -314,19 +459,57 @@ impl_new!(SuperExpr, (keyword: Token, method: Token), {
 impl_new!(ClassDec, (name: String, methods: HashMap<String, FunStmt>, superclass: Option<ClassDec> ), {
     methods,
     name,
-    superclass: superclass.map_or(None, |s| Some(Box::new(s)))
+    superclass: superclass.map_or(None, |s| Some(Box::new(s))),
+    class_methods: HashMap::new(),
+    getters: HashMap::new(),
+    fields: HashMap::new(),
+    id: None,
 } );
 
-impl_new!(ClassStmt, (name: Token, methods: Vec<FunStmt>, superclass: Option<VarExpr>) );
+impl_new!(ClassStmt, (
+    name: Token,
+    methods: Vec<FunStmt>,
+    class_methods: Vec<FunStmt>,
+    getters: Vec<FunStmt>,
+    fields: Vec<VarStmt>,
+    superclass: Option<VarExpr>
+) );
 
 impl_new!(ReturnStmt, (keyword: Token, value: Expr) );
 
+impl_new!(PrintStmt, (keyword: Token, value: Expr) );
+
+impl_new!(ThrowStmt, (keyword: Token, value: Expr) );
+
+impl_new!(YieldStmt, (keyword: Token, value: Expr) );
+
+impl_new!(TryStmt, (body: Stmt, catch: Option<CatchClause>, finally: Option<Stmt>), {
+    body: Box::new(body),
+    catch,
+    finally: finally.map(Box::new),
+});
+
+impl_new!(CatchClause, (name: Token, body: Stmt), {
+    name,
+    body: Box::new(body),
+});
+
+impl_new!(ImportStmt, (keyword: Token, path: String, names: Vec<Token>, namespace: Option<Token>) );
+
 impl_new!(VarStmt, (name: Token, val: Expr) );
 
-impl_new!(NativeFn, (
-    arity: u8,
-    action: fn(&mut Interpreter, Vec<LiteralExpr>) -> Result<LiteralExpr, LoxError>
-));
+impl NativeFn {
+    pub fn new<F>(name: &str, arity: Arity, action: F) -> Self
+    where
+        F: Fn(&mut Interpreter, Vec<LiteralExpr>) -> Result<LiteralExpr, LoxError> + 'static,
+    {
+        Self {
+            name: name.to_string(),
+            arity,
+            action: Rc::new(action),
+        }
+    }
+}
 
 impl_new!(IfStmt, (condition: Expr, then_b: Stmt, else_b: Stmt), {
     condition,
@@ -343,6 +526,7 @@ impl_new!(FunStmt, (name: Token, params: Vec<Token>, body: Stmt, is_init: bool),
     name,
     params,
     is_init,
+    is_generator: false,
     closure: None,
     body: Box::new(body),
 });
@@ -358,6 +542,42 @@ impl_new!(SetExpr, (object: Expr, name: Token, value: Expr), {
     value: Box::new(value),
 } );
 
+impl_new!(ListExpr, (bracket: Token, elements: Vec<Expr>) );
+
+impl_new!(MapExpr, (brace: Token, entries: Vec<(Expr, Expr)>) );
+
+impl_new!(IndexExpr, (object: Expr, bracket: Token, index: Expr), {
+    object: Box::new(object),
+    bracket,
+    index: Box::new(index),
+} );
+
+impl_new!(SetIndexExpr, (object: Expr, bracket: Token, index: Expr, value: Expr), {
+    object: Box::new(object),
+    bracket,
+    index: Box::new(index),
+    value: Box::new(value),
+} );
+
+impl_new!(LambdaExpr, (keyword: Token, params: Vec<Token>, body: Stmt), {
+    function: FunStmt::new(
+        Token::new(TokenType::Identifier, "lambda".to_string(), keyword.line),
+        params,
+        body,
+        false,
+    ),
+} );
+
+impl_new!(StringifyExpr, (quote: Token, expression: Expr), {
+    quote,
+    expression: Box::new(expression),
+} );
+
+impl_new!(IterExpr, (keyword: Token, expression: Expr), {
+    keyword,
+    expression: Box::new(expression),
+} );
+
 impl_new!(GetExpr, (object: Expr, name: Token), {
     object: Box::new(object),
     name,
@@ -389,7 +609,8 @@ impl_new!(BinaryExpr, (left: Expr, operator: Token, right: Expr), {
 impl_new!(ClassInstance, (dec: ClassDec, id: usize), {
     dec,
     fields: HashMap::new(),
-    id
+    id,
+    module: None
 });
 
 impl_new!(LogicalExpr, (left: Expr, operator: Token, right: Expr), {
@@ -416,11 +637,24 @@ impl Stmt {
             Self::Class(class) => {
                 format!("(class {})", class.name.lexeme)
             }
+            Self::Import(import) => format!("(import {})", import.path),
+            Self::Throw(throw) => format!("(throw {})", throw.value.print()),
+            Self::Yield(yield_) => format!("(yield {})", yield_.value.print()),
+            Self::Try(try_) => {
+                let mut text = format!("(try {}", try_.body.print());
+                if let Some(catch) = try_.catch {
+                    text += &format!(" (catch {} {})", catch.name.lexeme, catch.body.print());
+                }
+                if let Some(finally) = try_.finally {
+                    text += &format!(" (finally {})", finally.print());
+                }
+                text + ")"
+            }
             Stmt::Return(return_stmt) => {
                 format!("(return {})", return_stmt.value.print())
             }
             Stmt::Expression(expr) => expr.print(),
-            Stmt::Print(expr) => format!("(print {})", expr.print()),
+            Stmt::Print(print) => format!("(print {})", print.value.print()),
             Stmt::Var(var_stmt) => {
                 format!(
                     "(var {} = {})",
@@ -477,6 +711,40 @@ impl Expr {
     pub fn print(self) -> String {
         match self {
             Expr::Super(super_expr) => format!("(super {})", super_expr.method.lexeme),
+            Expr::List(list) => {
+                let elements: Vec<String> = list.elements.into_iter().map(|e| e.print()).collect();
+                format!("(list {})", elements.join(", "))
+            }
+            Expr::Map(map) => {
+                let entries: Vec<String> = map
+                    .entries
+                    .into_iter()
+                    .map(|(k, v)| format!("{}: {}", k.print(), v.print()))
+                    .collect();
+                format!("(map {})", entries.join(", "))
+            }
+            Expr::Index(index) => {
+                format!("(index {} {})", index.object.print(), index.index.print())
+            }
+            Expr::SetIndex(set) => format!(
+                "(set-index {} {} {})",
+                set.object.print(),
+                set.index.print(),
+                set.value.print()
+            ),
+            Expr::Stringify(stringify) => {
+                AstPrinter::parenthesize("str", vec![stringify.expression])
+            }
+            Expr::Iter(iter) => AstPrinter::parenthesize("iter", vec![iter.expression]),
+            Expr::Lambda(lambda) => {
+                let params: Vec<String> = lambda
+                    .function
+                    .params
+                    .iter()
+                    .map(|p| p.lexeme.clone())
+                    .collect();
+                format!("(lambda ({}) {{}})", params.join(", "))
+            }
             Expr::This(this_expr) => format!("(this {})", this_expr.keyword.line),
             Expr::Set(set_expr) => {
                 format!("(set {})", set_expr.name)
@@ -540,6 +808,9 @@ impl Expr {
                 LiteralExpr::String(str) => str.to_string(),
                 LiteralExpr::Call(_) => "<callable>".to_string(),
                 LiteralExpr::Instance(_) => "<instance>".to_string(),
+                LiteralExpr::Generator(_) => "<generator>".to_string(),
+                LiteralExpr::List(_) => "<list>".to_string(),
+                LiteralExpr::Map(_) => "<map>".to_string(),
             },
             Expr::Unary(unary) => {
                 let UnaryExpr { operator, right } = unary;
@@ -574,10 +845,10 @@ impl Stmt {
                 writeln!(f, "ExprStmt")?;
                 expr.fmt_indented(f, level + 1)
             }
-            Stmt::Print(expr) => {
+            Stmt::Print(s) => {
                 pad(f, level)?;
                 writeln!(f, "Print")?;
-                expr.fmt_indented(f, level + 1)
+                s.value.fmt_indented(f, level + 1)
             }
             Stmt::Var(v) => {
                 pad(f, level)?;
@@ -602,6 +873,32 @@ impl Stmt {
                 writeln!(f, "Else:")?;
                 s.else_b.fmt_indented(f, level + 2)
             }
+            Stmt::Throw(s) => {
+                pad(f, level)?;
+                writeln!(f, "Throw")?;
+                s.value.fmt_indented(f, level + 1)
+            }
+            Stmt::Yield(s) => {
+                pad(f, level)?;
+                writeln!(f, "Yield")?;
+                s.value.fmt_indented(f, level + 1)
+            }
+            Stmt::Try(s) => {
+                pad(f, level)?;
+                writeln!(f, "Try")?;
+                s.body.fmt_indented(f, level + 1)?;
+                if let Some(catch) = &s.catch {
+                    pad(f, level)?;
+                    writeln!(f, "Catch {}", catch.name.lexeme)?;
+                    catch.body.fmt_indented(f, level + 1)?;
+                }
+                if let Some(finally) = &s.finally {
+                    pad(f, level)?;
+                    writeln!(f, "Finally")?;
+                    finally.fmt_indented(f, level + 1)?;
+                }
+                Ok(())
+            }
             Stmt::While(s) => {
                 pad(f, level)?;
                 writeln!(f, "While")?;
@@ -636,13 +933,34 @@ impl Stmt {
                 writeln!(f, "Return")?;
                 r.value.fmt_indented(f, level + 1)
             }
+            Stmt::Import(i) => {
+                pad(f, level)?;
+                writeln!(f, "Import {}", i.path)?;
+                for name in &i.names {
+                    pad(f, level + 1)?;
+                    writeln!(f, "Name {}", name.lexeme)?;
+                }
+                Ok(())
+            }
             Stmt::Class(c) => {
                 pad(f, level)?;
                 writeln!(f, "Class {}", c.name.lexeme)?;
-                for method in &c.methods {
+                for field in &c.fields {
                     pad(f, level + 1)?;
-                    writeln!(f, "Method {}", method.name.lexeme)?;
-                    method.body.fmt_indented(f, level + 2)?;
+                    writeln!(f, "Field {}", field.name.lexeme)?;
+                    field.val.fmt_indented(f, level + 2)?;
+                }
+                let members = [
+                    ("Method", &c.methods),
+                    ("ClassMethod", &c.class_methods),
+                    ("Getter", &c.getters),
+                ];
+                for (kind, methods) in members {
+                    for method in methods {
+                        pad(f, level + 1)?;
+                        writeln!(f, "{kind} {}", method.name.lexeme)?;
+                        method.body.fmt_indented(f, level + 2)?;
+                    }
                 }
                 Ok(())
             }
@@ -663,6 +981,57 @@ impl Expr {
                 pad(f, level)?;
                 writeln!(f, "Super {}", s.method)
             }
+            Expr::List(l) => {
+                pad(f, level)?;
+                writeln!(f, "List")?;
+                for element in &l.elements {
+                    element.fmt_indented(f, level + 1)?;
+                }
+                Ok(())
+            }
+            Expr::Map(m) => {
+                pad(f, level)?;
+                writeln!(f, "Map")?;
+                for (key, value) in &m.entries {
+                    key.fmt_indented(f, level + 1)?;
+                    value.fmt_indented(f, level + 2)?;
+                }
+                Ok(())
+            }
+            Expr::Index(i) => {
+                pad(f, level)?;
+                writeln!(f, "Index")?;
+                i.object.fmt_indented(f, level + 1)?;
+                i.index.fmt_indented(f, level + 1)
+            }
+            Expr::SetIndex(s) => {
+                pad(f, level)?;
+                writeln!(f, "SetIndex")?;
+                s.object.fmt_indented(f, level + 1)?;
+                s.index.fmt_indented(f, level + 1)?;
+                s.value.fmt_indented(f, level + 1)
+            }
+            Expr::Stringify(s) => {
+                pad(f, level)?;
+                writeln!(f, "Stringify")?;
+                s.expression.fmt_indented(f, level + 1)
+            }
+            Expr::Iter(i) => {
+                pad(f, level)?;
+                writeln!(f, "Iter")?;
+                i.expression.fmt_indented(f, level + 1)
+            }
+            Expr::Lambda(l) => {
+                pad(f, level)?;
+                let params: Vec<&str> = l
+                    .function
+                    .params
+                    .iter()
+                    .map(|p| p.lexeme.as_str())
+                    .collect();
+                writeln!(f, "Lambda ({})", params.join(", "))?;
+                l.function.body.fmt_indented(f, level + 1)
+            }
             Expr::Assign(a) => {
                 pad(f, level)?;
                 writeln!(f, "Assign {} (depth: {:?})", a.name.lexeme, a.depth)?;
@@ -736,6 +1105,9 @@ impl fmt::Display for LiteralExpr {
             LiteralExpr::String(s) => write!(f, "\"{}\"", s),
             LiteralExpr::Call(_) => write!(f, "<callable>"),
             LiteralExpr::Instance(_) => write!(f, "<instance>"),
+            LiteralExpr::Generator(_) => write!(f, "<generator>"),
+            LiteralExpr::List(_) => write!(f, "<list>"),
+            LiteralExpr::Map(_) => write!(f, "<map>"),
         }
     }
 }
@@ -745,6 +1117,9 @@ impl LiteralExpr {
         match self {
             LiteralExpr::Call(_) => write!(f, "<callable>"),
             LiteralExpr::Instance(_) => write!(f, "<instance>"),
+            LiteralExpr::Generator(_) => write!(f, "<generator>"),
+            LiteralExpr::List(_) => write!(f, "<list>"),
+            LiteralExpr::Map(_) => write!(f, "<map>"),
             _ => writeln!(f, "{}", self),
         }
     }
@@ -756,7 +1131,36 @@ impl LiteralExpr {
 
 impl PartialEq for NativeFn {
     fn eq(&self, other: &Self) -> bool {
-        self.arity == other.arity
+        self.name == other.name && Rc::ptr_eq(&self.action, &other.action)
+    }
+}
+
+impl fmt::Debug for NativeFn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NativeFn")
+            .field("name", &self.name)
+            .field("arity", &self.arity)
+            .finish()
+    }
+}
+
+impl Arity {
+    pub fn accepts(&self, count: usize) -> bool {
+        match self {
+            Arity::Exact(n) => count == *n,
+            Arity::Range(min, max) => (*min..=*max).contains(&count),
+            Arity::Variadic => true,
+        }
+    }
+}
+
+impl fmt::Display for Arity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Arity::Exact(n) => write!(f, "{n}"),
+            Arity::Range(min, max) => write!(f, "{min} to {max}"),
+            Arity::Variadic => write!(f, "any number of"),
+        }
     }
 }
 
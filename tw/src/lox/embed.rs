@@ -0,0 +1,235 @@
+use std::{io::Write, path::PathBuf};
+
+use crate::{
+    errors::LoxError,
+    lox::{
+        ast::{Arity, LiteralExpr, NativeFn, Stmt},
+        env::MAIN,
+        fs::Permissions,
+        interpreter::Interpreter,
+        native::NativeModule,
+        parser::Parser,
+        resolver::Resolver,
+        scanner::Scanner,
+    },
+};
+
+pub type Value = LiteralExpr;
+
+/**
+ * Entry point to host Lox scripts from Rust.
+ *
+ * The interpreter state (globals, classes, closures) is kept between calls to `eval`, so a host can
+ * load a script once and then call its functions or read its variables. Errors are always returned
+ * to the caller, nothing here reports them or ends the process unless the host calls `report`.
+ */
+pub struct Lox {
+    interpreter: Interpreter,
+}
+
+impl Default for Lox {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Lox {
+    pub fn new() -> Self {
+        Self {
+            interpreter: Interpreter::new(),
+        }
+    }
+
+    /// Runs `source` and returns the value of its last statement when it's an expression, `nil` otherwise
+    pub fn eval(&mut self, source: &str) -> Result<Value, LoxError> {
+        let result = self.eval_source(source);
+
+        if result.is_err() {
+            // A failed statement may leave the interpreter inside a nested scope
+            self.interpreter.env.curr_node = MAIN;
+        }
+
+        result
+    }
+
+    pub fn get_global(&self, name: &str) -> Option<Value> {
+        self.interpreter.env.get_global(name)
+    }
+
+    pub fn set_global(&mut self, name: &str, value: Value) {
+        self.interpreter.env.define_global(name.to_string(), value);
+    }
+
+    pub fn call_function(&mut self, name: &str, args: Vec<Value>) -> Result<Value, LoxError> {
+        let callee = self.get_global(name).unwrap_or(LiteralExpr::Nil);
+
+        let result = self.interpreter.call_value(callee, args, 0);
+        if result.is_err() {
+            self.interpreter.env.curr_node = MAIN;
+        }
+
+        result
+    }
+
+    pub fn define_native<F>(&mut self, name: &str, arity: Arity, action: F)
+    where
+        F: Fn(&mut Interpreter, Vec<Value>) -> Result<Value, LoxError> + 'static,
+    {
+        self.interpreter
+            .define_native(NativeFn::new(name, arity, action));
+    }
+
+    pub fn register_module(&mut self, module: &dyn NativeModule) {
+        self.interpreter.register_module(module);
+    }
+
+    /// Adds a directory where `import` looks for modules
+    pub fn add_search_path(&mut self, path: impl Into<PathBuf>) {
+        self.interpreter.add_search_path(path);
+    }
+
+    /// Arguments the script reads with `args()`
+    pub fn set_args(&mut self, args: Vec<String>) {
+        self.interpreter.set_args(args);
+    }
+
+    /// Lets scripts read or write files and standard input, see `FsModule`
+    pub fn set_permissions(&mut self, permissions: Permissions) {
+        self.interpreter.set_permissions(permissions);
+    }
+
+    /// Sends the output of `print` statements to `sink` instead of stdout
+    pub fn set_output(&mut self, sink: impl Write + 'static) {
+        self.interpreter.set_output(sink);
+    }
+
+    /// Sends the errors given to `report` to `sink` instead of stderr
+    pub fn set_error_output(&mut self, sink: impl Write + 'static) {
+        self.interpreter.set_error_output(sink);
+    }
+
+    /// Writes `error` to the error sink, for hosts that want diagnostics instead of handling errors
+    pub fn report(&mut self, error: &LoxError) {
+        self.interpreter.report(error);
+    }
+
+    fn eval_source(&mut self, source: &str) -> Result<Value, LoxError> {
+        let tokens = Scanner::try_scan_from(source.to_string())?;
+        let mut stmts = Parser::new(tokens).parse()?;
+
+        // The resolver owns the interpreter while it runs, so it has to be given back even on errors
+        let mut resolver = Resolver::new(std::mem::take(&mut self.interpreter));
+        let resolved = resolver.resolve_stmts(&mut stmts);
+        self.interpreter = resolver.interpreter;
+        resolved?;
+
+        let last_expr = match stmts.last() {
+            Some(Stmt::Expression(_)) => match stmts.pop() {
+                Some(Stmt::Expression(expr)) => Some(expr),
+                _ => None,
+            },
+            _ => None,
+        };
+
+        self.interpreter.interpret(stmts)?;
+
+        match last_expr {
+            Some(expr) => self.interpreter.evaluate(expr),
+            None => Ok(LiteralExpr::Nil),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lox::Capture;
+
+    #[test]
+    fn test_eval_returns_last_expression() {
+        let mut lox = Lox::new();
+
+        let val = lox.eval("var a = 2; a * 21;").expect("eval failed");
+        assert_eq!(val, Value::Number(42.0));
+
+        let val = lox.eval("var b = 1;").expect("eval failed");
+        assert_eq!(val, Value::Nil);
+    }
+
+    #[test]
+    fn test_state_is_kept_between_evals() {
+        let mut lox = Lox::new();
+
+        lox.eval("var count = 1;").expect("eval failed");
+        lox.eval("count = count + 1;").expect("eval failed");
+
+        assert_eq!(lox.get_global("count"), Some(Value::Number(2.0)));
+    }
+
+    #[test]
+    fn test_set_global_and_call_function() {
+        let mut lox = Lox::new();
+        lox.set_global("greeting", Value::String("Hello".to_string()));
+
+        lox.eval("fun greet(name) { return greeting + \", \" + name; }")
+            .expect("eval failed");
+
+        let val = lox
+            .call_function("greet", vec![Value::String("Lox".to_string())])
+            .expect("call failed");
+        assert_eq!(val, Value::String("Hello, Lox".to_string()));
+    }
+
+    #[test]
+    fn test_print_goes_to_output_sink() {
+        let out = Capture::new();
+        let mut lox = Lox::new();
+        lox.set_output(out.clone());
+
+        lox.eval("print 1 + 2; print \"two\";")
+            .expect("eval failed");
+        assert_eq!(out.contents(), "3\ntwo\n");
+
+        out.clear();
+        lox.eval("print nil;").expect("eval failed");
+        assert_eq!(out.contents(), "nil\n");
+    }
+
+    #[test]
+    fn test_report_goes_to_error_sink() {
+        let (out, err) = (Capture::new(), Capture::new());
+        let mut lox = Lox::new();
+        lox.set_output(out.clone());
+        lox.set_error_output(err.clone());
+
+        let error = lox.eval("print 1; nope;").expect_err("eval should fail");
+        lox.report(&error);
+
+        assert_eq!(out.contents(), "1\n");
+        assert_eq!(err.contents(), format!("{error}\n"));
+        assert!(
+            err.contents().starts_with("RUNTIME |"),
+            "{}",
+            err.contents()
+        );
+    }
+
+    #[test]
+    fn test_errors_are_returned() {
+        let mut lox = Lox::new();
+
+        assert!(matches!(lox.eval("var a = ;"), Err(LoxError::Parse(_))));
+        assert!(matches!(lox.eval("\"open"), Err(LoxError::Scan(_))));
+        assert!(matches!(
+            lox.eval("{ var a = 1; a / 0; }"),
+            Err(LoxError::Runtime(_))
+        ));
+        assert!(matches!(
+            lox.call_function("missing", vec![]),
+            Err(LoxError::Runtime(_))
+        ));
+
+        // The interpreter is still usable after an error
+        assert_eq!(lox.eval("1 + 1;").expect("eval failed"), Value::Number(2.0));
+    }
+}
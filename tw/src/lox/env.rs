@@ -23,6 +23,11 @@ use std::fmt;
 pub type EnvBindings = HashMap<String, LiteralExpr>;
 pub type EnvId = usize;
 
+/// Node with the natives, every module (the main script too) looks names up here last
+pub const PRELUDE: EnvId = 0;
+/// Root node of the script being run, imported modules get roots of their own
+pub const MAIN: EnvId = 1;
+
 #[derive(Clone, Debug)]
 pub struct EnvNode {
     pub values: EnvBindings,
@@ -46,11 +51,9 @@ impl EnvNode {
 
 impl Default for Environment {
     fn default() -> Self {
-        let globals = EnvNode::new();
-
         Self {
-            nodes: vec![globals],
-            curr_node: 0,
+            nodes: vec![EnvNode::new(), EnvNode::new()],
+            curr_node: MAIN,
         }
     }
 }
@@ -62,16 +65,54 @@ impl Environment {
         scope.values.insert(name, value);
     }
 
+    /// Looks up a global of the running module, falling back to the prelude (natives)
     pub fn get(&self, name: &Token) -> Result<LiteralExpr, LoxError> {
-        let env = &self.nodes[0];
-
-        if let Some(value) = env.values.get(&name.lexeme.clone()) {
-            return Ok(value.to_owned());
+        for env_id in [self.globals(), PRELUDE] {
+            if let Some(value) = self.nodes[env_id].values.get(&name.lexeme) {
+                return Ok(value.to_owned());
+            }
         }
 
         Err(RuntimeError::UndefinedVariable(name.lexeme.clone()).at(name.line))
     }
 
+    /**
+     * Each module runs in its own root node, so the globals of the code being executed are not
+     * always in `MAIN`, they are in the root of the chain the current node belongs to.
+     */
+    pub fn globals(&self) -> EnvId {
+        let mut curr = self.curr_node;
+
+        while let Some(parent) = self.nodes[curr].parent {
+            curr = parent;
+        }
+
+        curr
+    }
+
+    /// Adds a node with no parent and makes it the current one, the global scope of a module
+    pub fn push_root(&mut self) -> EnvId {
+        self.nodes.push(EnvNode::new());
+        self.curr_node = self.nodes.len() - 1;
+
+        self.curr_node
+    }
+
+    /// Global of the main script, or a native when the script doesn't define that name
+    pub fn get_global(&self, name: &str) -> Option<LiteralExpr> {
+        [MAIN, PRELUDE]
+            .into_iter()
+            .find_map(|env_id| self.nodes[env_id].values.get(name).cloned())
+    }
+
+    pub fn define_global(&mut self, name: String, value: LiteralExpr) {
+        self.nodes[MAIN].values.insert(name, value);
+    }
+
+    pub fn define_prelude(&mut self, name: String, value: LiteralExpr) {
+        self.nodes[PRELUDE].values.insert(name, value);
+    }
+
     pub fn get_at(&self, at: usize, name: &Token) -> Result<LiteralExpr, LoxError> {
         let Some(lit) = self.nodes[self.ancestor(at)].values.get(&name.lexeme) else {
             return Err(RuntimeError::UndefinedVariable(name.lexeme.clone()).at(name.line));
@@ -103,7 +144,8 @@ impl Environment {
     }
 
     pub fn assign(&mut self, name: Token, value: LiteralExpr) -> Result<(), LoxError> {
-        let env = &mut self.nodes[0];
+        let globals = self.globals();
+        let env = &mut self.nodes[globals];
 
         if env.values.contains_key(&name.lexeme.clone()) {
             env.values.insert(name.lexeme.clone(), value);
@@ -151,7 +193,7 @@ impl Environment {
         if let Some(parent_id) = curr_env.parent {
             self.curr_node = parent_id;
         } else {
-            self.curr_node = 0
+            self.curr_node = MAIN
         }
     }
 }
@@ -0,0 +1,205 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, BufRead, Write},
+};
+
+use crate::{
+    errors::{Locate, LoxError, RuntimeError},
+    lox::{
+        ast::{Arity, LiteralExpr, NativeFn, Object},
+        interpreter::Interpreter,
+        native::{NativeModule, string},
+    },
+};
+
+/// What scripts may touch outside the interpreter, nothing by default
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Permissions {
+    /// `readLine`, `readFile`, `fileExists` and `listDir`
+    pub read: bool,
+    /// `writeFile` and `appendFile`
+    pub write: bool,
+}
+
+/**
+ * Standard input and file system natives. They are always defined, but fail with a runtime error
+ * unless the interpreter has the permission they need (`--allow-read` and `--allow-write` in the CLI).
+ * Relative paths are resolved from the working directory, not from the script.
+ */
+pub struct FsModule;
+
+impl NativeModule for FsModule {
+    fn natives(&self) -> Vec<NativeFn> {
+        vec![
+            // The line without its line break, `nil` once the input has ended
+            NativeFn::new("readLine", Arity::Exact(0), |inter, _| {
+                allow_read(inter, "readLine")?;
+
+                let mut line = String::new();
+                let read = io::stdin()
+                    .lock()
+                    .read_line(&mut line)
+                    .map_err(|err| file_error("read", "stdin", err))?;
+                if read == 0 {
+                    return Ok(LiteralExpr::Nil);
+                }
+
+                let len = line.trim_end_matches(['\n', '\r']).len();
+                line.truncate(len);
+                Ok(LiteralExpr::String(line))
+            }),
+            NativeFn::new("readFile", Arity::Exact(1), |inter, args| {
+                allow_read(inter, "readFile")?;
+                let path = string(&args[0])?;
+
+                fs::read_to_string(path)
+                    .map(LiteralExpr::String)
+                    .map_err(|err| file_error("read", path, err))
+            }),
+            NativeFn::new("fileExists", Arity::Exact(1), |inter, args| {
+                allow_read(inter, "fileExists")?;
+                let path = string(&args[0])?;
+
+                fs::exists(path)
+                    .map(LiteralExpr::Boolean)
+                    .map_err(|err| file_error("check", path, err))
+            }),
+            // Names of the entries in the directory, sorted
+            NativeFn::new("listDir", Arity::Exact(1), |inter, args| {
+                allow_read(inter, "listDir")?;
+                let path = string(&args[0])?;
+
+                let mut names = Vec::new();
+                let entries = fs::read_dir(path).map_err(|err| file_error("list", path, err))?;
+                for entry in entries {
+                    let entry = entry.map_err(|err| file_error("list", path, err))?;
+                    names.push(entry.file_name().to_string_lossy().to_string());
+                }
+                names.sort();
+
+                let names = names.into_iter().map(LiteralExpr::String).collect();
+                Ok(inter.alloc(Object::List(names)))
+            }),
+            NativeFn::new("writeFile", Arity::Exact(2), |inter, args| {
+                allow_write(inter, "writeFile")?;
+                let (path, text) = (string(&args[0])?, string(&args[1])?);
+
+                fs::write(path, text).map_err(|err| file_error("write", path, err))?;
+                Ok(LiteralExpr::Nil)
+            }),
+            // Creates the file when it doesn't exist yet
+            NativeFn::new("appendFile", Arity::Exact(2), |inter, args| {
+                allow_write(inter, "appendFile")?;
+                let (path, text) = (string(&args[0])?, string(&args[1])?);
+
+                OpenOptions::new()
+                    .append(true)
+                    .create(true)
+                    .open(path)
+                    .and_then(|mut file| file.write_all(text.as_bytes()))
+                    .map_err(|err| file_error("write", path, err))?;
+                Ok(LiteralExpr::Nil)
+            }),
+        ]
+    }
+}
+
+fn allow_read(inter: &Interpreter, native: &str) -> Result<(), LoxError> {
+    if inter.permissions.read {
+        return Ok(());
+    }
+
+    Err(RuntimeError::PermissionDenied(native.to_string(), "--allow-read".to_string()).at(0))
+}
+
+fn allow_write(inter: &Interpreter, native: &str) -> Result<(), LoxError> {
+    if inter.permissions.write {
+        return Ok(());
+    }
+
+    Err(RuntimeError::PermissionDenied(native.to_string(), "--allow-write".to_string()).at(0))
+}
+
+fn file_error(action: &str, path: &str, err: io::Error) -> LoxError {
+    RuntimeError::FileError {
+        action: action.to_string(),
+        path: path.to_string(),
+        message: err.to_string(),
+    }
+    .at(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use crate::{
+        errors::RuntimeError,
+        lox::{Lox, Permissions, Value, test_util::runtime_error_with},
+    };
+
+    fn with_permissions(read: bool, write: bool) -> Lox {
+        let mut lox = Lox::new();
+        lox.set_permissions(Permissions { read, write });
+
+        lox
+    }
+
+    #[test]
+    fn test_files_round_trip() {
+        let dir = env::temp_dir().join(format!("lox-fs-test-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let dir_path = dir.to_string_lossy().replace('\\', "/");
+
+        let mut lox = with_permissions(true, true);
+        let res = lox.eval(&format!(
+            "
+            var file = \"{dir_path}/notes.txt\";
+            var existed = fileExists(file);
+            writeFile(file, \"one\\n\");
+            appendFile(file, \"two\\n\");
+            appendFile(\"{dir_path}/other.txt\", \"\");
+            str([existed, fileExists(file), readFile(file), listDir(\"{dir_path}\")]);
+            "
+        ));
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            res.unwrap(),
+            Value::String(
+                "[false, true, \"one\ntwo\n\", [\"notes.txt\", \"other.txt\"]]".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_fs_permissions() {
+        assert_eq!(
+            runtime_error_with(with_permissions(false, false), "readFile(\"x.txt\");"),
+            RuntimeError::PermissionDenied("readFile".to_string(), "--allow-read".to_string())
+        );
+
+        assert_eq!(
+            runtime_error_with(with_permissions(true, false), "writeFile(\"x.txt\", \"\");"),
+            RuntimeError::PermissionDenied("writeFile".to_string(), "--allow-write".to_string())
+        );
+    }
+
+    #[test]
+    fn test_fs_errors_carry_the_os_message() {
+        let src = "readFile(\"/no/such/dir/file.txt\");";
+        let err = runtime_error_with(with_permissions(true, true), src);
+
+        let RuntimeError::FileError {
+            action,
+            path,
+            message,
+        } = err
+        else {
+            panic!("expected a file error, got {err:?}");
+        };
+        assert_eq!(action, "read");
+        assert_eq!(path, "/no/such/dir/file.txt");
+        assert!(!message.is_empty());
+    }
+}
@@ -0,0 +1,171 @@
+use std::mem;
+
+use crate::{
+    errors::{Locate, LoxError, RuntimeError},
+    lox::{
+        ast::{Arity, LiteralExpr, NativeFn, Object, Stmt},
+        env::EnvId,
+        interpreter::{ExecResult, Interpreter},
+        token::Token,
+    },
+};
+
+/**
+ * A tree-walker can't pause in the middle of `execute`, the Rust stack that got it there is gone
+ * once `yield` returns. Instead, each statement between the function body and the `yield` leaves a
+ * note of where it was while the `Yield` result unwinds, and the next resume walks the same path
+ * back down, skipping what already ran. Blocks keep their environment node, so locals survive.
+ */
+#[derive(Debug, PartialEq, Clone)]
+pub enum Resume {
+    /// Statement `index` of a block, running in the node `env`
+    Block {
+        index: usize,
+        env: EnvId,
+    },
+    /// Inside the body of a loop, the condition is not evaluated again until the body ends
+    While,
+    If {
+        then_b: bool,
+    },
+    /// In the try body, or in the catch block when `catch_env` holds the node binding the error
+    Try {
+        catch_env: Option<EnvId>,
+    },
+    /// The `yield` that suspended, it finishes as soon as it is resumed
+    Yield,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Generator {
+    pub body: Vec<Stmt>,
+    /// Node with the arguments of the call that created the generator
+    pub env: EnvId,
+    /// Where the last `yield` left the body, the outermost statement is the last one
+    pub resume: Vec<Resume>,
+    /// Value `hasNext()` had to run the body for, the next `next()` returns it
+    pub buffered: Option<LiteralExpr>,
+    pub running: bool,
+    pub done: bool,
+}
+
+impl Generator {
+    pub fn new(body: Vec<Stmt>, env: EnvId) -> Self {
+        Self {
+            body,
+            env,
+            resume: Vec::new(),
+            buffered: None,
+            running: false,
+            done: false,
+        }
+    }
+}
+
+/// `gen.done` tells if the body has finished, its methods are in `generator_method`
+pub fn generator_get(
+    inter: &mut Interpreter,
+    gen_id: usize,
+    name: &Token,
+) -> Result<LiteralExpr, LoxError> {
+    match name.lexeme.as_str() {
+        "done" => Ok(LiteralExpr::Boolean(generator(inter, gen_id)?.done)),
+        _ => Err(RuntimeError::UndefinedProperty(name.lexeme.clone()).at(name.line)),
+    }
+}
+
+/**
+ * `gen.next()` runs the body until the next `yield`.
+ *
+ * A generator is also an iterator for `for-in` loops. `hasNext()` can only know by running the body
+ * to the next `yield`, so it keeps that value for the following `next()`. The value a body returns
+ * is not an item: when the body ends during `hasNext()` there is nothing next.
+ */
+pub fn generator_method(gen_id: usize, name: &str) -> Option<NativeFn> {
+    let method = match name {
+        "next" => NativeFn::new("next", Arity::Exact(0), move |inter, _| {
+            match generator(inter, gen_id)?.buffered.take() {
+                Some(value) => Ok(value),
+                None => resume(inter, gen_id),
+            }
+        }),
+        "hasNext" => NativeFn::new("hasNext", Arity::Exact(0), move |inter, _| {
+            let generator = generator(inter, gen_id)?;
+            if generator.buffered.is_some() || generator.done {
+                return Ok(LiteralExpr::Boolean(generator.buffered.is_some()));
+            }
+
+            let value = resume(inter, gen_id)?;
+            let generator = self::generator(inter, gen_id)?;
+            if !generator.done {
+                generator.buffered = Some(value);
+            }
+
+            Ok(LiteralExpr::Boolean(!generator.done))
+        }),
+        "iterator" => NativeFn::new("iterator", Arity::Exact(0), move |_, _| {
+            Ok(LiteralExpr::Generator(gen_id))
+        }),
+        _ => return None,
+    };
+
+    Some(method)
+}
+
+/// Returns the next yielded value, or the returned one when the body ends
+pub fn resume(inter: &mut Interpreter, gen_id: usize) -> Result<LiteralExpr, LoxError> {
+    let generator = generator(inter, gen_id)?;
+    if generator.done {
+        return Ok(LiteralExpr::Nil);
+    }
+    if generator.running {
+        return Err(RuntimeError::GeneratorRunning.at(0));
+    }
+
+    generator.running = true;
+    let body = generator.body.clone();
+    let env = generator.env;
+    let path = mem::take(&mut generator.resume);
+
+    let previous = inter.env.curr_node;
+    let outer = mem::replace(&mut inter.resume, path);
+
+    inter.env.curr_node = env;
+    let result = inter.execute_block(body);
+    inter.env.curr_node = previous;
+
+    let path = mem::replace(&mut inter.resume, outer);
+
+    let generator = self::generator(inter, gen_id)?;
+    generator.running = false;
+
+    match result {
+        Ok(ExecResult::Yield(value)) => {
+            generator.resume = path;
+
+            Ok(value)
+        }
+        Ok(ExecResult::Return(value)) => {
+            generator.done = true;
+
+            Ok(value)
+        }
+        Ok(ExecResult::Normal) => {
+            generator.done = true;
+
+            Ok(LiteralExpr::Nil)
+        }
+        Err(err) => {
+            generator.done = true;
+
+            Err(err)
+        }
+    }
+}
+
+fn generator(inter: &mut Interpreter, gen_id: usize) -> Result<&mut Generator, LoxError> {
+    match &mut inter.heap[gen_id] {
+        Object::Generator(generator) => Ok(generator),
+        _ => Err(RuntimeError::NotAnInstance.at(0)),
+    }
+}
@@ -1,15 +1,36 @@
-use std::collections::HashMap;
-use std::time::{SystemTime, UNIX_EPOCH};
-
-use crate::errors::{Locate, LoxError, RuntimeError};
+use std::{
+    collections::HashMap,
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use crate::errors::{IoError, Locate, LocateResult, LoxError, RuntimeError};
 use crate::lox::ast::*;
-use crate::lox::env::{EnvBindings, Environment};
+use crate::lox::env::{EnvBindings, EnvId, Environment};
+use crate::lox::fs::{FsModule, Permissions};
+use crate::lox::generator::{Generator, Resume, generator_get, generator_method};
+use crate::lox::iterator::iterator;
+use crate::lox::list::{list_index, list_method};
+use crate::lox::map::{LoxMap, MapKey, map_method};
+use crate::lox::math::MathModule;
+use crate::lox::module::{Module, Modules, display_path};
+use crate::lox::native::{CoreModule, NativeModule, integral};
+use crate::lox::output::{ErrorOutput, Output};
+use crate::lox::parser::Parser;
+use crate::lox::process::ProcessModule;
+use crate::lox::reflect::ReflectModule;
+use crate::lox::resolver::Resolver;
+use crate::lox::scanner::Scanner;
+use crate::lox::string::{StringModule, string_method};
 use crate::lox::token::*;
 
 #[derive(Debug)]
 pub enum ExecResult {
     Normal,
     Return(LiteralExpr),
+    /// A generator suspended, `Interpreter::resume` has the way back to the `yield`
+    Yield(LiteralExpr),
 }
 
 /**
@@ -22,26 +43,64 @@ pub enum ExecResult {
 pub struct Interpreter {
     pub(crate) env: Environment,
     pub(crate) heap: Vec<Object>,
-}
-
-fn clock(_: &mut Interpreter, _: Vec<LiteralExpr>) -> Result<LiteralExpr, LoxError> {
-    let time = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .as_secs_f64();
-
-    Ok(LiteralExpr::Number(time))
+    out: Output,
+    err: ErrorOutput,
+    modules: Modules,
+    /// Resume points of a generator, filled while a `yield` unwinds and consumed when it resumes
+    pub(crate) resume: Vec<Resume>,
+    pub(crate) permissions: Permissions,
+    pub(crate) script_args: Vec<String>,
 }
 
 impl Interpreter {
     pub fn new() -> Self {
         let mut interpreter = Self::default();
 
-        interpreter.assign_objet("clock".to_string(), NativeFn::new(0, clock).into());
+        interpreter.register_module(&CoreModule);
+        interpreter.register_module(&ReflectModule);
+        interpreter.register_module(&StringModule);
+        interpreter.register_module(&MathModule);
+        interpreter.register_module(&FsModule);
+        interpreter.register_module(&ProcessModule);
 
         interpreter
     }
 
+    /// Sends the output of `print` statements to `sink` instead of stdout
+    pub fn set_output(&mut self, sink: impl Write + 'static) {
+        self.out = Output::new(sink);
+    }
+
+    /// Sends the errors given to `report` to `sink` instead of stderr
+    pub fn set_error_output(&mut self, sink: impl Write + 'static) {
+        self.err = ErrorOutput::new(sink);
+    }
+
+    /// Writes `error` to the error sink, errors are still returned and never reported on their own
+    pub fn report(&mut self, error: &LoxError) {
+        let _ = writeln!(self.err, "{error}");
+    }
+
+    /// Lets scripts read or write files and standard input, see `FsModule`
+    pub fn set_permissions(&mut self, permissions: Permissions) {
+        self.permissions = permissions;
+    }
+
+    /// Arguments of the script, returned by `args()`
+    pub fn set_args(&mut self, args: Vec<String>) {
+        self.script_args = args;
+    }
+
+    /// Sets the file being run, relative imports are resolved from its directory
+    pub fn set_script_path(&mut self, path: &Path) {
+        self.modules.enter(path);
+    }
+
+    /// Adds a directory where imports are looked up when they are not relative to the importing file
+    pub fn add_search_path(&mut self, path: impl Into<PathBuf>) {
+        self.modules.add_search_path(path);
+    }
+
     pub fn interpret(&mut self, stmts: Vec<Stmt>) -> Result<(), LoxError> {
         for stmt in stmts {
             self.execute(stmt)?;
@@ -89,7 +148,20 @@ impl Interpreter {
             })
             .collect();
 
-        let class = ClassDec::new(class_stmt.name.lexeme, methods, superclass.clone());
+        let closure = self.env.curr_node;
+        let members = |funs: Vec<FunStmt>| -> HashMap<String, FunStmt> {
+            funs.into_iter()
+                .map(|mut f| {
+                    f.closure = Some(closure);
+                    (f.name.lexeme.clone(), f)
+                })
+                .collect()
+        };
+
+        let mut class = ClassDec::new(class_stmt.name.lexeme, methods, superclass.clone());
+        class.class_methods = members(class_stmt.class_methods);
+        class.getters = members(class_stmt.getters);
+
         if let Some(_) = superclass {
             let curr_env = &self.env.nodes[self.env.curr_node];
 
@@ -98,11 +170,99 @@ impl Interpreter {
             }
         }
 
-        self.assign_objet(class.name.clone(), class.into());
+        let name = class.name.clone();
+        class.id = Some(self.heap.len());
+        let class_lit = self.alloc(class.into());
+        self.env.define(name, class_lit.clone());
+
+        let LiteralExpr::Call(class_id) = class_lit else {
+            unreachable!("classes are allocated as callables")
+        };
+        for field in class_stmt.fields {
+            let val = self.evaluate(field.val)?;
+
+            if let Object::Callable(Callable::Class(class)) = &mut self.heap[class_id] {
+                class.fields.insert(field.name.lexeme, val);
+            }
+        }
+
+        Ok(ExecResult::Normal)
+    }
+
+    fn import_statement(&mut self, import: ImportStmt) -> Result<ExecResult, LoxError> {
+        let line = import.keyword.line;
+
+        let Some(path) = self.modules.resolve(&import.path) else {
+            return Err(RuntimeError::ModuleNotFound(import.path).at(line));
+        };
+
+        let module = self.load_module(path, line)?;
+
+        if let Some(namespace) = import.namespace {
+            self.env.define(namespace.lexeme, module.namespace);
+        }
+
+        for name in import.names {
+            let Some(value) = self.env.nodes[module.env].values.get(&name.lexeme).cloned() else {
+                return Err(RuntimeError::UndefinedExport(import.path, name.lexeme).at(name.line));
+            };
+
+            self.env.define(name.lexeme, value);
+        }
 
         Ok(ExecResult::Normal)
     }
 
+    fn load_module(&mut self, path: PathBuf, line: usize) -> Result<Module, LoxError> {
+        if let Some(module) = self.modules.get(&path) {
+            return Ok(module.clone());
+        }
+
+        if let Some(cycle) = self.modules.cycle(&path) {
+            return Err(RuntimeError::ImportCycle(cycle).at(line));
+        }
+
+        let source = fs::read_to_string(&path).map_err(IoError::Sys)?;
+
+        self.modules.enter(&path);
+        let result = self.run_module(source);
+        self.modules.exit();
+
+        let env = result.map_err(|err| err.in_file(&display_path(&path)))?;
+
+        let name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let module = Module {
+            env,
+            namespace: self.module_object(&name, env),
+        };
+        self.modules.insert(path, module.clone());
+
+        Ok(module)
+    }
+
+    /// Runs a module in a new root node of the environment and returns it
+    fn run_module(&mut self, source: String) -> Result<usize, LoxError> {
+        let tokens = Scanner::try_scan_from(source)?;
+        let mut stmts = Parser::new(tokens).parse()?;
+
+        // Same as in `Lox::eval`, the resolver owns the interpreter while it runs
+        let mut resolver = Resolver::new(std::mem::take(self));
+        let resolved = resolver.resolve_stmts(&mut stmts);
+        *self = resolver.interpreter;
+        resolved?;
+
+        let previous = self.env.curr_node;
+        let root = self.env.push_root();
+
+        let result = self.interpret(stmts);
+        self.env.curr_node = previous;
+
+        result.map(|_| root)
+    }
+
     fn return_statement(&mut self, return_stmt: ReturnStmt) -> Result<ExecResult, LoxError> {
         let mut val = LiteralExpr::Nil;
 
@@ -113,6 +273,105 @@ impl Interpreter {
         Ok(ExecResult::Return(val))
     }
 
+    fn throw_statement(&mut self, throw: ThrowStmt) -> Result<ExecResult, LoxError> {
+        let value = self.evaluate(throw.value)?;
+        let text = self.display(&value, throw.keyword.line)?;
+
+        Err(RuntimeError::Thrown { value, text }.at(throw.keyword.line))
+    }
+
+    /**
+     * Thrown values travel through the `Err` side, the same path as the runtime errors, so they cross
+     * function calls and natives without extra checks. `finally` runs whatever the body and the
+     * catch block ended with, and a `return` or `throw` inside it replaces that outcome.
+     */
+    fn try_statement(&mut self, try_stmt: TryStmt) -> Result<ExecResult, LoxError> {
+        let env = self.env.curr_node;
+
+        let mut catch_env = None;
+        let mut result = match self.resume.last() {
+            Some(&Resume::Try {
+                catch_env: Some(node),
+            }) => {
+                self.resume.pop();
+                catch_env = Some(node);
+                Ok(ExecResult::Normal)
+            }
+            Some(Resume::Try { catch_env: None }) => {
+                self.resume.pop();
+                self.execute(*try_stmt.body)
+            }
+            _ => self.execute(*try_stmt.body),
+        };
+
+        if let Some(catch) = try_stmt.catch {
+            if let Err(err) = result {
+                self.env.curr_node = env;
+
+                result = self.caught_value(err).map(|value| {
+                    self.env.push_node();
+                    self.env.define(catch.name.lexeme, value);
+                    catch_env = Some(self.env.curr_node);
+
+                    ExecResult::Normal
+                });
+            }
+
+            if let Some(node) = catch_env {
+                self.env.curr_node = node;
+                result = self.execute(*catch.body);
+            }
+        }
+
+        self.env.curr_node = env;
+
+        // A generator suspended, `finally` waits until the body or the catch block really end
+        if let Ok(ExecResult::Yield(_)) = result {
+            self.resume.push(Resume::Try { catch_env });
+            return result;
+        }
+
+        if let Some(finally) = try_stmt.finally
+            && let ExecResult::Return(val) = self.execute(*finally)?
+        {
+            return Ok(ExecResult::Return(val));
+        }
+
+        result
+    }
+
+    fn yield_statement(&mut self, yield_stmt: YieldStmt) -> Result<ExecResult, LoxError> {
+        if self.resume.last() == Some(&Resume::Yield) {
+            self.resume.pop();
+            return Ok(ExecResult::Normal);
+        }
+
+        let value = self.evaluate(yield_stmt.value)?;
+        self.resume.push(Resume::Yield);
+
+        Ok(ExecResult::Yield(value))
+    }
+
+    /// Value bound by `catch`, runtime errors become an `Error` instance with `message` and `line`
+    fn caught_value(&mut self, err: LoxError) -> Result<LiteralExpr, LoxError> {
+        let LoxError::Runtime(located) = err else {
+            return Err(err);
+        };
+
+        if let RuntimeError::Thrown { value, .. } = located.error {
+            return Ok(value);
+        }
+
+        let mut fields = HashMap::new();
+        fields.insert(
+            "message".to_string(),
+            LiteralExpr::String(located.error.to_string()),
+        );
+        fields.insert("line".to_string(), LiteralExpr::Number(located.line as f64));
+
+        Ok(self.namespace_object("Error", fields))
+    }
+
     fn fun_statement(&mut self, mut fun_stmt: FunStmt) -> Result<ExecResult, LoxError> {
         let fn_name = fun_stmt.name.lexeme.clone();
 
@@ -126,12 +385,24 @@ impl Interpreter {
     fn if_statement(&mut self, if_stmt: IfStmt) -> Result<ExecResult, LoxError> {
         let mut result = ExecResult::Normal;
 
-        if Self::is_truthy(self.evaluate(if_stmt.condition)?)? {
+        let then_b = match self.resume.last() {
+            Some(&Resume::If { then_b }) => {
+                self.resume.pop();
+                then_b
+            }
+            _ => Self::is_truthy(self.evaluate(if_stmt.condition)?)?,
+        };
+
+        if then_b {
             result = self.execute(*if_stmt.then_b)?;
         } else if *if_stmt.else_b != LiteralExpr::Nil.into() {
             result = self.execute(*if_stmt.else_b)?;
         }
 
+        if let ExecResult::Yield(_) = result {
+            self.resume.push(Resume::If { then_b });
+        }
+
         Ok(result)
     }
 
@@ -145,11 +416,21 @@ impl Interpreter {
     fn while_statement(&mut self, while_stmt: WhileStmt) -> Result<ExecResult, LoxError> {
         let WhileStmt { condition, body } = while_stmt;
 
-        while Self::is_truthy(self.evaluate(condition.clone())?)? {
-            let result = self.execute(*body.clone())?;
+        let mut resumed = self.resume.last() == Some(&Resume::While);
+        if resumed {
+            self.resume.pop();
+        }
+
+        while resumed || Self::is_truthy(self.evaluate(condition.clone())?)? {
+            resumed = false;
 
-            if let ExecResult::Return(_) = result {
-                return Ok(result);
+            match self.execute(*body.clone())? {
+                ExecResult::Normal => {}
+                ExecResult::Return(val) => return Ok(ExecResult::Return(val)),
+                ExecResult::Yield(val) => {
+                    self.resume.push(Resume::While);
+                    return Ok(ExecResult::Yield(val));
+                }
             }
         }
 
@@ -162,9 +443,10 @@ impl Interpreter {
         Ok(ExecResult::Normal)
     }
 
-    fn print_statement(&mut self, expr: Expr) -> Result<ExecResult, LoxError> {
-        let val: Expr = self.evaluate(expr)?.into();
-        println!("{}", val.print());
+    fn print_statement(&mut self, print: PrintStmt) -> Result<ExecResult, LoxError> {
+        let val = self.evaluate(print.value)?;
+        let text = self.display(&val, print.keyword.line)?;
+        writeln!(self.out, "{text}").map_err(IoError::Sys)?;
 
         Ok(ExecResult::Normal)
     }
@@ -212,58 +494,219 @@ impl Interpreter {
     }
 
     fn set_expr(&mut self, set: SetExpr) -> Result<LiteralExpr, LoxError> {
-        let LiteralExpr::Instance(obj_id) = self.evaluate(*set.object)? else {
+        let (LiteralExpr::Instance(obj_id) | LiteralExpr::Call(obj_id)) =
+            self.evaluate(*set.object)?
+        else {
             return Err(RuntimeError::NotAnInstance.at(set.name.line));
         };
 
         let val = self.evaluate(*set.value)?;
-        if let Object::Instance(obj) = &mut self.heap[obj_id] {
-            obj.set(set.name, val.clone());
+        match &mut self.heap[obj_id] {
+            Object::Instance(obj) => obj.set(set.name, val.clone(), &mut self.env),
+            Object::Callable(Callable::Class(class)) => {
+                class.fields.insert(set.name.lexeme, val.clone());
+            }
+            _ => return Err(RuntimeError::NotAnInstance.at(set.name.line)),
         }
 
         Ok(val)
     }
 
     fn get_expr(&mut self, get: GetExpr) -> Result<LiteralExpr, LoxError> {
-        let LiteralExpr::Instance(obj_id) = self.evaluate(*get.object)? else {
-            return Err(RuntimeError::NotAnInstance.at(get.name.line));
+        let object = self.evaluate(*get.object)?;
+
+        self.get_property(object, get.name)
+    }
+
+    /// `object.name`, methods of builtin values are allocated as bound natives, see `builtin_method`
+    fn get_property(&mut self, object: LiteralExpr, name: Token) -> Result<LiteralExpr, LoxError> {
+        if let Some(method) = builtin_method(&object, &name.lexeme) {
+            return Ok(self.alloc(method.into()));
+        }
+
+        let obj_id = match object {
+            LiteralExpr::Instance(obj_id) => obj_id,
+            LiteralExpr::List(_) | LiteralExpr::Map(_) | LiteralExpr::String(_) => {
+                return Err(RuntimeError::UndefinedProperty(name.lexeme).at(name.line));
+            }
+            LiteralExpr::Call(class_id) => return self.static_get(class_id, name),
+            LiteralExpr::Generator(gen_id) => return generator_get(self, gen_id, &name),
+            _ => return Err(RuntimeError::NotAnInstance.at(name.line)),
         };
 
         let Object::Instance(obj) = self.heap[obj_id].clone() else {
-            return Err(RuntimeError::NotAnInstance.at(get.name.line));
+            return Err(RuntimeError::NotAnInstance.at(name.line));
         };
 
-        let val = obj.get(&get.name, self)?;
+        obj.get(&name, self)
+    }
 
-        Ok(val)
+    /// Static fields and class methods, read on the class object
+    fn static_get(&mut self, class_id: usize, name: Token) -> Result<LiteralExpr, LoxError> {
+        let Object::Callable(Callable::Class(class)) = &self.heap[class_id] else {
+            return Err(RuntimeError::NotAnInstance.at(name.line));
+        };
+
+        if let Some(val) = class.fields.get(&name.lexeme) {
+            return Ok(val.clone());
+        }
+
+        let Some(method) = class.find_class_method(&name.lexeme) else {
+            return Err(RuntimeError::UndefinedProperty(name.lexeme).at(name.line));
+        };
+
+        Ok(self.alloc(method.into()))
     }
 
+    fn list_expr(&mut self, list: ListExpr) -> Result<LiteralExpr, LoxError> {
+        let mut items = Vec::new();
+        for element in list.elements {
+            items.push(self.evaluate(element)?);
+        }
+
+        Ok(self.alloc(Object::List(items)))
+    }
+
+    fn index_expr(&mut self, index: IndexExpr) -> Result<LiteralExpr, LoxError> {
+        let object = self.evaluate(*index.object)?;
+        let position = self.evaluate(*index.index)?;
+
+        let line = index.bracket.line;
+        if let Some(value) =
+            self.call_special(&object, "__index__", vec![position.clone()], line)?
+        {
+            return Ok(value);
+        }
+
+        let (LiteralExpr::List(obj_id) | LiteralExpr::Map(obj_id)) = object else {
+            return Err(RuntimeError::NotIndexable.at(line));
+        };
+
+        match &self.heap[obj_id] {
+            Object::List(items) => {
+                let i = list_index(&position, items.len()).at(line)?;
+                Ok(items[i].clone())
+            }
+            Object::Map(map) => {
+                let key = MapKey::from_value(&position).at(line)?;
+
+                map.get(&key)
+                    .cloned()
+                    .ok_or_else(|| RuntimeError::UndefinedKey(position.to_string()).at(line))
+            }
+            _ => Err(RuntimeError::NotIndexable.at(line)),
+        }
+    }
+
+    fn set_index_expr(&mut self, set: SetIndexExpr) -> Result<LiteralExpr, LoxError> {
+        let object = self.evaluate(*set.object)?;
+        let position = self.evaluate(*set.index)?;
+        let value = self.evaluate(*set.value)?;
+
+        let line = set.bracket.line;
+        let args = vec![position.clone(), value.clone()];
+        if self
+            .call_special(&object, "__setindex__", args, line)?
+            .is_some()
+        {
+            return Ok(value);
+        }
+
+        let (LiteralExpr::List(obj_id) | LiteralExpr::Map(obj_id)) = object else {
+            return Err(RuntimeError::NotIndexable.at(line));
+        };
+
+        match &mut self.heap[obj_id] {
+            Object::List(items) => {
+                let i = list_index(&position, items.len()).at(line)?;
+                items[i] = value.clone();
+            }
+            Object::Map(map) => {
+                let key = MapKey::from_value(&position).at(line)?;
+                map.insert(key, value.clone());
+            }
+            _ => return Err(RuntimeError::NotIndexable.at(line)),
+        }
+
+        Ok(value)
+    }
+
+    fn map_expr(&mut self, map_expr: MapExpr) -> Result<LiteralExpr, LoxError> {
+        let mut map = LoxMap::new();
+
+        for (key, value) in map_expr.entries {
+            let key = self.evaluate(key)?;
+            let key = MapKey::from_value(&key).at(map_expr.brace.line)?;
+            let value = self.evaluate(value)?;
+
+            map.insert(key, value);
+        }
+
+        Ok(self.alloc(Object::Map(map)))
+    }
+
+    /**
+     * `xs.push(x)` calls the method of the list right away. Reading `xs.push` first would leave a
+     * bound native on the heap for every call, and the heap is never collected.
+     */
     fn call_expr(&mut self, call: CallExpr) -> Result<LiteralExpr, LoxError> {
-        let callee = self.evaluate(*call.callee)?;
+        let line = call.paren.line;
+        let callee = match *call.callee {
+            Expr::Get(get) => {
+                let object = self.evaluate(*get.object)?;
+
+                match builtin_method(&object, &get.name.lexeme) {
+                    Some(method) => {
+                        let arguments = self.evaluate_args(call.args)?;
+                        return self.call_callable(Callable::Native(method), arguments, line);
+                    }
+                    None => self.get_property(object, get.name)?,
+                }
+            }
+            callee => self.evaluate(callee)?,
+        };
 
+        let arguments = self.evaluate_args(call.args)?;
+        self.call_value(callee, arguments, line)
+    }
+
+    fn evaluate_args(&mut self, args: Vec<Expr>) -> Result<Vec<LiteralExpr>, LoxError> {
         let mut arguments = Vec::new();
-        for arg in call.args {
+        for arg in args {
             arguments.push(self.evaluate(arg)?);
         }
 
+        Ok(arguments)
+    }
+
+    pub(crate) fn call_value(
+        &mut self,
+        callee: LiteralExpr,
+        arguments: Vec<LiteralExpr>,
+        line: usize,
+    ) -> Result<LiteralExpr, LoxError> {
         let LiteralExpr::Call(callable_id) = callee else {
-            return Err(RuntimeError::NotCallable.at(call.paren.line));
+            return Err(RuntimeError::NotCallable.at(line));
         };
 
         let Object::Callable(fn_) = self.heap[callable_id].clone() else {
-            return Err(RuntimeError::NotCallable.at(call.paren.line));
+            return Err(RuntimeError::NotCallable.at(line));
         };
 
-        if arguments.len() != fn_.arity() {
-            return Err(
-                RuntimeError::ArgumentCountMismatch(fn_.arity(), arguments.len())
-                    .at(call.paren.line),
-            );
-        }
+        self.call_callable(fn_, arguments, line)
+    }
 
-        let val = fn_.call(self, arguments)?;
+    fn call_callable(
+        &mut self,
+        fn_: Callable,
+        arguments: Vec<LiteralExpr>,
+        line: usize,
+    ) -> Result<LiteralExpr, LoxError> {
+        if !fn_.arity().accepts(arguments.len()) {
+            return Err(RuntimeError::ArgumentCountMismatch(fn_.arity(), arguments.len()).at(line));
+        }
 
-        Ok(val)
+        fn_.call(self, arguments).map_err(|err| err.or_at(line))
     }
 
     fn assign_expr(&mut self, assign: AssignmentExpr) -> Result<LiteralExpr, LoxError> {
@@ -290,10 +733,54 @@ impl Interpreter {
         self.evaluate(*group.expression)
     }
 
+    /// Lambdas close over the environment they are evaluated in, like declared functions
+    fn lambda_expr(&mut self, lambda: LambdaExpr) -> Result<LiteralExpr, LoxError> {
+        let mut function = lambda.function;
+        function.closure = Some(self.env.curr_node);
+
+        Ok(self.alloc(function.into()))
+    }
+
+    fn stringify_expr(&mut self, stringify: StringifyExpr) -> Result<LiteralExpr, LoxError> {
+        let value = self.evaluate(*stringify.expression)?;
+
+        let text = self.display(&value, stringify.quote.line)?;
+
+        Ok(LiteralExpr::String(text))
+    }
+
+    fn iter_expr(&mut self, iter: IterExpr) -> Result<LiteralExpr, LoxError> {
+        let value = self.evaluate(*iter.expression)?;
+
+        iterator(self, value, iter.keyword.line)
+    }
+
     fn binary_expr(&mut self, binary: BinaryExpr) -> Result<LiteralExpr, LoxError> {
         let left_expr = self.evaluate(*binary.left)?;
         let right_expr = self.evaluate(*binary.right)?;
 
+        let line = binary.operator.line;
+        if let Some(result) =
+            self.binary_overload(&binary.operator.type_, &left_expr, &right_expr, line)?
+        {
+            return match binary.operator.type_ {
+                TokenType::BangEqual => Ok(LiteralExpr::Boolean(!Self::is_truthy(result)?)),
+                _ => Ok(result),
+            };
+        }
+
+        match binary.operator.type_ {
+            TokenType::EqualEqual => {
+                return Ok(LiteralExpr::Boolean(Self::is_equal(left_expr, right_expr)?));
+            }
+            TokenType::BangEqual => {
+                return Ok(LiteralExpr::Boolean(!Self::is_equal(
+                    left_expr, right_expr,
+                )?));
+            }
+            _ => {}
+        }
+
         if binary.operator.type_ == TokenType::Plus {
             match (left_expr, right_expr) {
                 (LiteralExpr::String(left_str), LiteralExpr::String(right_str)) => {
@@ -304,6 +791,14 @@ impl Interpreter {
                     let str = format!("{left_str}{right_num}");
                     return Ok(LiteralExpr::String(str));
                 }
+                (LiteralExpr::String(left_str), right @ LiteralExpr::Instance(_)) => {
+                    let str = format!("{left_str}{}", self.display(&right, line)?);
+                    return Ok(LiteralExpr::String(str));
+                }
+                (left @ LiteralExpr::Instance(_), LiteralExpr::String(right_str)) => {
+                    let str = format!("{}{right_str}", self.display(&left, line)?);
+                    return Ok(LiteralExpr::String(str));
+                }
                 (LiteralExpr::Number(left_num), LiteralExpr::Number(right_num)) => {
                     return Ok(LiteralExpr::Number(left_num + right_num));
                 }
@@ -318,7 +813,6 @@ impl Interpreter {
 
         let right_num = match right_expr {
             LiteralExpr::Number(num) => num,
-            LiteralExpr::String(ref str) => str.len() as f64,
             _ => return Err(RuntimeError::NumberExpected.at(binary.operator.line)),
         };
 
@@ -331,18 +825,27 @@ impl Interpreter {
                 Ok(LiteralExpr::Number(left_num / right_num))
             }
             TokenType::Star => Ok(LiteralExpr::Number(left_num * right_num)),
+            TokenType::TildeSlash => {
+                if right_num == 0.0 {
+                    return Err(RuntimeError::DivisionByZero.at(binary.operator.line));
+                }
+                Ok(LiteralExpr::Number((left_num / right_num).floor()))
+            }
+            TokenType::StarStar => Ok(LiteralExpr::Number(left_num.powf(right_num))),
+            TokenType::Ampersand
+            | TokenType::Pipe
+            | TokenType::Caret
+            | TokenType::LessLess
+            | TokenType::GreaterGreater => {
+                let value = bitwise(&binary.operator.type_, left_num, right_num)
+                    .at(binary.operator.line)?;
+                Ok(LiteralExpr::Number(value))
+            }
 
             TokenType::Greater => Ok(LiteralExpr::Boolean(left_num > right_num)),
             TokenType::GreaterEqual => Ok(LiteralExpr::Boolean(left_num >= right_num)),
             TokenType::Less => Ok(LiteralExpr::Boolean(left_num < right_num)),
             TokenType::LessEqual => Ok(LiteralExpr::Boolean(left_num <= right_num)),
-
-            TokenType::BangEqual => Ok(LiteralExpr::Boolean(!Interpreter::is_equal(
-                left_expr, right_expr,
-            )?)),
-            TokenType::EqualEqual => Ok(LiteralExpr::Boolean(Interpreter::is_equal(
-                left_expr, right_expr,
-            )?)),
             _ => Ok(LiteralExpr::Nil),
         }
     }
@@ -364,9 +867,26 @@ impl Interpreter {
     fn unary_expr(&mut self, unary: UnaryExpr) -> Result<LiteralExpr, LoxError> {
         let right = self.evaluate(*unary.right)?;
 
+        let method = match unary.operator.type_ {
+            TokenType::Minus => Some("__neg__"),
+            TokenType::Tilde => Some("__invert__"),
+            _ => None,
+        };
+        if let Some(method) = method
+            && let Some(result) =
+                self.call_special(&right, method, Vec::new(), unary.operator.line)?
+        {
+            return Ok(result);
+        }
+
         match (unary.operator.type_, right) {
             (TokenType::Minus, LiteralExpr::Number(num)) => Ok(LiteralExpr::Number(-num)),
             (TokenType::Minus, _) => Err(RuntimeError::NumberExpected.at(unary.operator.line)),
+            (TokenType::Tilde, LiteralExpr::Number(num)) => {
+                let int = integral(num).at(unary.operator.line)?;
+                Ok(LiteralExpr::Number(!int as f64))
+            }
+            (TokenType::Tilde, _) => Err(RuntimeError::NumberExpected.at(unary.operator.line)),
             (TokenType::Bang, lit) => {
                 let bool_val = Interpreter::is_truthy(lit)?;
                 Ok(LiteralExpr::Boolean(!bool_val))
@@ -387,6 +907,9 @@ impl Interpreter {
             LiteralExpr::Nil => Ok(false),
             LiteralExpr::Call(_) => Ok(true),
             LiteralExpr::Instance(_) => Ok(true),
+            LiteralExpr::Generator(_) => Ok(true),
+            LiteralExpr::List(_) => Ok(true),
+            LiteralExpr::Map(_) => Ok(true),
         }
     }
 
@@ -401,10 +924,13 @@ impl Interpreter {
         }
     }
 
-    fn evaluate(&mut self, expr: Expr) -> Result<LiteralExpr, LoxError> {
+    pub(crate) fn evaluate(&mut self, expr: Expr) -> Result<LiteralExpr, LoxError> {
         match expr {
             Expr::Binary(binary) => self.binary_expr(binary),
             Expr::Grouping(group) => self.grouping_expr(group),
+            Expr::Stringify(stringify) => self.stringify_expr(stringify),
+            Expr::Iter(iter) => self.iter_expr(iter),
+            Expr::Lambda(lambda) => self.lambda_expr(lambda),
             Expr::Literal(literal) => Self::literal_expr(literal),
             Expr::Unary(unary) => self.unary_expr(unary),
             Expr::Var(var) => self.var_expr(var),
@@ -415,21 +941,48 @@ impl Interpreter {
             Expr::Set(set) => self.set_expr(set),
             Expr::This(this) => self.this_expr(this),
             Expr::Super(super_) => self.super_expr(super_),
+            Expr::List(list) => self.list_expr(list),
+            Expr::Map(map) => self.map_expr(map),
+            Expr::Index(index) => self.index_expr(index),
+            Expr::SetIndex(set) => self.set_index_expr(set),
         }
     }
 
-    fn execute_block(&mut self, stmts: Vec<Stmt>) -> Result<ExecResult, LoxError> {
-        self.env.push_node();
+    pub(crate) fn execute_block(&mut self, stmts: Vec<Stmt>) -> Result<ExecResult, LoxError> {
+        let start = match self.resume.last() {
+            Some(&Resume::Block { index, env }) => {
+                self.resume.pop();
+                self.env.curr_node = env;
+                index
+            }
+            _ => {
+                self.env.push_node();
+                0
+            }
+        };
 
-        for stmt in stmts {
+        for (index, stmt) in stmts.into_iter().enumerate().skip(start) {
             let result = match self.execute(stmt) {
                 Ok(res) => res,
-                Err(some) => some.report_and_exit(1),
+                Err(err) => {
+                    self.env.pop_node();
+                    return Err(err);
+                }
             };
 
-            if let ExecResult::Return(_) = result {
-                self.env.pop_node();
-                return Ok(result);
+            match result {
+                ExecResult::Normal => {}
+                ExecResult::Return(_) => {
+                    self.env.pop_node();
+                    return Ok(result);
+                }
+                ExecResult::Yield(_) => {
+                    let env = self.env.curr_node;
+                    self.resume.push(Resume::Block { index, env });
+
+                    self.env.pop_node();
+                    return Ok(result);
+                }
             }
         }
 
@@ -449,28 +1002,233 @@ impl Interpreter {
             Stmt::Function(fn_) => self.fun_statement(fn_),
             Stmt::Return(return_stmt) => self.return_statement(return_stmt),
             Stmt::Class(class_stmt) => self.class_statement(class_stmt),
+            Stmt::Import(import) => self.import_statement(import),
+            Stmt::Throw(throw) => self.throw_statement(throw),
+            Stmt::Try(try_stmt) => self.try_statement(try_stmt),
+            Stmt::Yield(yield_stmt) => self.yield_statement(yield_stmt),
+        }
+    }
+
+    /**
+     * Text `print`, interpolation and concatenation show. Instances can provide theirs with a
+     * `toString` method, or `__str__` as for the other special methods, which must return a string.
+     * `line` is the one of the expression that asked for the text.
+     */
+    pub(crate) fn display(&mut self, value: &LiteralExpr, line: usize) -> Result<String, LoxError> {
+        for method in ["toString", "__str__"] {
+            match self.call_special(value, method, Vec::new(), line)? {
+                Some(LiteralExpr::String(text)) => return Ok(text),
+                Some(_) => return Err(RuntimeError::NotAString(method.to_string()).at(line)),
+                None => {}
+            }
+        }
+
+        Ok(self.stringify(value))
+    }
+
+    /**
+     * Calls the method `name` of an instance operand, `None` when the operand is not an instance
+     * or its class doesn't define that method. Only methods count, a field holding a function
+     * doesn't overload anything.
+     */
+    /// Result of the method overloading `operator`, the right operand's reflected method is the fallback
+    fn binary_overload(
+        &mut self,
+        operator: &TokenType,
+        left: &LiteralExpr,
+        right: &LiteralExpr,
+        line: usize,
+    ) -> Result<Option<LiteralExpr>, LoxError> {
+        if let Some(method) = operator_method(operator)
+            && let Some(result) = self.call_special(left, method, vec![right.clone()], line)?
+        {
+            return Ok(Some(result));
+        }
+
+        match reflected_method(operator) {
+            Some(method) => self.call_special(right, method, vec![left.clone()], line),
+            None => Ok(None),
+        }
+    }
+
+    fn call_special(
+        &mut self,
+        operand: &LiteralExpr,
+        name: &str,
+        args: Vec<LiteralExpr>,
+        line: usize,
+    ) -> Result<Option<LiteralExpr>, LoxError> {
+        let LiteralExpr::Instance(obj_id) = operand else {
+            return Ok(None);
+        };
+        let Object::Instance(instance) = &self.heap[*obj_id] else {
+            return Ok(None);
+        };
+        let Some(mut method) = instance.dec.find_method(name.to_string()) else {
+            return Ok(None);
+        };
+
+        method.bind(*obj_id, &mut self.env);
+        let method = self.alloc(method.into());
+
+        self.call_value(method, args, line).map(Some)
+    }
+
+    /// Text shown by `print`, lists and maps are shown with their contents
+    pub(crate) fn stringify(&self, value: &LiteralExpr) -> String {
+        match value {
+            LiteralExpr::List(_) | LiteralExpr::Map(_) => {
+                self.stringify_item(value, &mut Vec::new())
+            }
+            LiteralExpr::Call(_) | LiteralExpr::Instance(_) => self.describe(value),
+            other => {
+                let expr: Expr = other.clone().into();
+                expr.print()
+            }
+        }
+    }
+
+    /// Values inside collections, strings are quoted so `["1"]` and `[1]` look different
+    fn stringify_item(&self, value: &LiteralExpr, seen: &mut Vec<usize>) -> String {
+        let (LiteralExpr::List(obj_id) | LiteralExpr::Map(obj_id)) = value else {
+            return self.describe(value);
+        };
+
+        // A collection that contains itself would never finish printing
+        if seen.contains(obj_id) {
+            return match value {
+                LiteralExpr::Map(_) => "{...}".to_string(),
+                _ => "[...]".to_string(),
+            };
+        }
+
+        seen.push(*obj_id);
+        let text = match &self.heap[*obj_id] {
+            Object::List(items) => {
+                let items: Vec<String> = items
+                    .iter()
+                    .map(|item| self.stringify_item(item, seen))
+                    .collect();
+
+                format!("[{}]", items.join(", "))
+            }
+            Object::Map(map) => {
+                let entries: Vec<String> = map
+                    .entries()
+                    .map(|(key, val)| {
+                        let key = self.stringify_item(&key.to_value(), seen);
+                        format!("{key}: {}", self.stringify_item(val, seen))
+                    })
+                    .collect();
+
+                format!("{{{}}}", entries.join(", "))
+            }
+            _ => value.to_string(),
+        };
+        seen.pop();
+
+        text
+    }
+
+    /// Functions, classes and instances print their name, other values their literal
+    fn describe(&self, value: &LiteralExpr) -> String {
+        let (LiteralExpr::Call(obj_id) | LiteralExpr::Instance(obj_id)) = value else {
+            return value.to_string();
+        };
+
+        match &self.heap[*obj_id] {
+            Object::Callable(Callable::User(fun)) => format!("<fn {}>", fun.name.lexeme),
+            Object::Callable(Callable::Native(native)) => format!("<native fn {}>", native.name),
+            Object::Callable(Callable::Class(class)) => format!("<class {}>", class.name),
+            Object::Instance(instance) => format!("{} instance", instance.dec.name),
+            _ => value.to_string(),
         }
     }
 
     pub fn assign_objet(&mut self, name: String, obj: Object) {
+        let lit = self.alloc(obj);
+        self.env.define(name, lit);
+    }
+
+    /// Moves `obj` to the heap and returns the literal pointing to it
+    pub fn alloc(&mut self, obj: Object) -> LiteralExpr {
         let obj_id = self.heap.len();
 
         let lit = match obj {
             Object::Callable(_) => LiteralExpr::Call(obj_id),
             Object::Instance(_) => LiteralExpr::Instance(obj_id),
+            Object::List(_) => LiteralExpr::List(obj_id),
+            Object::Map(_) => LiteralExpr::Map(obj_id),
+            Object::Generator(_) => LiteralExpr::Generator(obj_id),
         };
 
         self.heap.push(obj);
-        self.env.define(name, lit);
+        lit
+    }
+
+    pub fn define_native(&mut self, native: NativeFn) {
+        let lit = self.alloc(native.clone().into());
+        self.env.define_prelude(native.name, lit);
+    }
+
+    /// Defines every native of `module` as a global, or as fields of a global object when the module has a namespace
+    pub fn register_module(&mut self, module: &dyn NativeModule) {
+        let natives = module.natives();
+        let constants = module.constants();
+
+        let Some(namespace) = module.namespace() else {
+            for native in natives {
+                self.define_native(native);
+            }
+            for (name, value) in constants {
+                self.env.define_prelude(name.to_string(), value);
+            }
+
+            return;
+        };
+
+        let mut fields: EnvBindings = constants
+            .into_iter()
+            .map(|(name, value)| (name.to_string(), value))
+            .collect();
+        for native in natives {
+            let lit = self.alloc(native.clone().into());
+            fields.insert(native.name, lit);
+        }
+
+        let object = self.namespace_object(namespace, fields);
+        self.env.define_prelude(namespace.to_string(), object);
+    }
+
+    /// Instance of an empty class named `name` holding `fields`, like namespaces (`math.sqrt`) and caught errors
+    pub(crate) fn namespace_object(&mut self, name: &str, fields: EnvBindings) -> LiteralExpr {
+        let obj_id = self.heap.len();
+        let dec = ClassDec::new(name.to_string(), HashMap::new(), None);
+
+        let mut instance = ClassInstance::new(dec, obj_id);
+        instance.fields = fields;
+
+        self.alloc(instance.into())
+    }
+
+    /// Namespace of `import "x" as name`, reads and writes go to the globals of the module
+    fn module_object(&mut self, name: &str, env: EnvId) -> LiteralExpr {
+        let obj_id = self.heap.len();
+        let dec = ClassDec::new(name.to_string(), HashMap::new(), None);
+
+        let mut instance = ClassInstance::new(dec, obj_id);
+        instance.module = Some(env);
+
+        self.alloc(instance.into())
     }
 }
 
 impl Callable {
-    pub fn arity(&self) -> usize {
+    pub fn arity(&self) -> Arity {
         match self {
-            Callable::User(fn_) => fn_.arity(),
-            Callable::Native(fn_) => fn_.arity as usize,
-            Callable::Class(class) => class.arity(),
+            Callable::User(fn_) => Arity::Exact(fn_.arity()),
+            Callable::Native(fn_) => fn_.arity,
+            Callable::Class(class) => Arity::Exact(class.arity()),
         }
     }
 
@@ -488,11 +1246,26 @@ impl Callable {
 }
 
 impl ClassInstance {
+    pub fn fields<'a>(&'a self, env: &'a Environment) -> &'a EnvBindings {
+        match self.module {
+            Some(node) => &env.nodes[node].values,
+            None => &self.fields,
+        }
+    }
+
     pub fn get(&self, name: &Token, inter: &mut Interpreter) -> Result<LiteralExpr, LoxError> {
-        if let Some(val) = self.fields.get(&name.lexeme.clone()) {
+        if let Some(val) = self.fields(&inter.env).get(&name.lexeme) {
             return Ok(val.clone());
         };
 
+        if let Some(mut getter) = self.dec.find_getter(&name.lexeme) {
+            getter.bind(self.id, &mut inter.env);
+
+            return getter
+                .call(inter, Vec::new())
+                .map_err(|err| err.or_at(name.line));
+        };
+
         if let Some(mut method) = self.dec.find_method(name.lexeme.clone()) {
             method.bind(self.id, &mut inter.env);
 
@@ -505,8 +1278,13 @@ impl ClassInstance {
         Err(RuntimeError::UndefinedProperty(name.lexeme.clone()).at(name.line))
     }
 
-    pub fn set(&mut self, name: Token, value: LiteralExpr) {
-        self.fields.insert(name.lexeme.clone(), value);
+    pub fn set(&mut self, name: Token, value: LiteralExpr, env: &mut Environment) {
+        let fields = match self.module {
+            Some(node) => &mut env.nodes[node].values,
+            None => &mut self.fields,
+        };
+
+        fields.insert(name.lexeme, value);
     }
 }
 
@@ -530,12 +1308,29 @@ impl ClassDec {
     }
 
     pub fn find_method(&self, name: String) -> Option<FunStmt> {
-        if let Some(method) = self.methods.get(&name).cloned() {
+        self.find_member(&name, |class| &class.methods)
+    }
+
+    pub fn find_class_method(&self, name: &str) -> Option<FunStmt> {
+        self.find_member(name, |class| &class.class_methods)
+    }
+
+    pub fn find_getter(&self, name: &str) -> Option<FunStmt> {
+        self.find_member(name, |class| &class.getters)
+    }
+
+    /// Looks `name` up in one kind of member, walking up the superclasses
+    fn find_member(
+        &self,
+        name: &str,
+        members: fn(&ClassDec) -> &HashMap<String, FunStmt>,
+    ) -> Option<FunStmt> {
+        if let Some(method) = members(self).get(name).cloned() {
             return Some(method);
         }
 
-        if let Some(superclass) = self.superclass.clone() {
-            return superclass.find_method(name);
+        if let Some(superclass) = &self.superclass {
+            return superclass.find_member(name, members);
         }
 
         None
@@ -567,6 +1362,17 @@ impl FunStmt {
             stmt => vec![stmt],
         };
 
+        // The body runs later, one `yield` at a time, in a node that keeps the arguments
+        if self.is_generator {
+            let env = exec.env.curr_node;
+            exec.env
+                .push_closure(fun_bindings, self.closure.unwrap_or(env));
+            let generator = Generator::new(stmts, exec.env.curr_node);
+            exec.env.curr_node = env;
+
+            return Ok(exec.alloc(Object::Generator(generator)));
+        }
+
         // To ensure the correct program execution we need the node when the function is called, because env.pop_node() only restores the environment to the state when the function was declared
         let previous = exec.env.curr_node;
 
@@ -577,6 +1383,7 @@ impl FunStmt {
         let result = exec.execute_block(stmts);
 
         exec.env.curr_node = previous;
+        let result = result?;
 
         if self.is_init {
             if let Some(closure) = self.closure {
@@ -586,7 +1393,7 @@ impl FunStmt {
             }
         }
 
-        if let ExecResult::Return(val) = result? {
+        if let ExecResult::Return(val) = result {
             return Ok(val);
         }
 
@@ -614,10 +1421,103 @@ impl FunStmt {
     }
 }
 
+/// Methods of builtin values, natives bound to the value they are read from
+fn builtin_method(value: &LiteralExpr, name: &str) -> Option<NativeFn> {
+    match value {
+        LiteralExpr::List(list_id) => list_method(*list_id, name),
+        LiteralExpr::Map(map_id) => map_method(*map_id, name),
+        LiteralExpr::String(text) => string_method(text.clone(), name),
+        LiteralExpr::Generator(gen_id) => generator_method(*gen_id, name),
+        _ => None,
+    }
+}
+
+/**
+ * Method that overloads a binary operator when the left operand is an instance, `!=` is the
+ * negation of `__eq__`. Without `__eq__`, instances are only equal to themselves.
+ */
+fn operator_method(operator: &TokenType) -> Option<&'static str> {
+    let name = match operator {
+        TokenType::Plus => "__add__",
+        TokenType::Minus => "__sub__",
+        TokenType::Star => "__mul__",
+        TokenType::Slash => "__div__",
+        TokenType::TildeSlash => "__floordiv__",
+        TokenType::StarStar => "__pow__",
+        TokenType::Ampersand => "__and__",
+        TokenType::Pipe => "__or__",
+        TokenType::Caret => "__xor__",
+        TokenType::LessLess => "__lshift__",
+        TokenType::GreaterGreater => "__rshift__",
+        TokenType::EqualEqual | TokenType::BangEqual => "__eq__",
+        TokenType::Less => "__lt__",
+        TokenType::LessEqual => "__le__",
+        TokenType::Greater => "__gt__",
+        TokenType::GreaterEqual => "__ge__",
+        _ => return None,
+    };
+
+    Some(name)
+}
+
+/**
+ * Method of the right operand called with the left one when the left operand has no overload,
+ * so `1 * m` calls `m.__rmul__(1)`. Equality is symmetric and comparisons swap, `1 < m` is `m > 1`.
+ */
+fn reflected_method(operator: &TokenType) -> Option<&'static str> {
+    let name = match operator {
+        TokenType::Plus => "__radd__",
+        TokenType::Minus => "__rsub__",
+        TokenType::Star => "__rmul__",
+        TokenType::Slash => "__rdiv__",
+        TokenType::TildeSlash => "__rfloordiv__",
+        TokenType::StarStar => "__rpow__",
+        TokenType::Ampersand => "__rand__",
+        TokenType::Pipe => "__ror__",
+        TokenType::Caret => "__rxor__",
+        TokenType::LessLess => "__rlshift__",
+        TokenType::GreaterGreater => "__rrshift__",
+        TokenType::EqualEqual | TokenType::BangEqual => "__eq__",
+        TokenType::Less => "__gt__",
+        TokenType::LessEqual => "__ge__",
+        TokenType::Greater => "__lt__",
+        TokenType::GreaterEqual => "__le__",
+        _ => return None,
+    };
+
+    Some(name)
+}
+
+/// Bitwise operators work on the `i64` value of integral numbers
+fn bitwise(operator: &TokenType, left: f64, right: f64) -> Result<f64, RuntimeError> {
+    let (left, right) = (integral(left)?, integral(right)?);
+
+    let value = match operator {
+        TokenType::Ampersand => left & right,
+        TokenType::Pipe => left | right,
+        TokenType::Caret => left ^ right,
+        TokenType::LessLess | TokenType::GreaterGreater => {
+            let shift = u32::try_from(right)
+                .ok()
+                .filter(|shift| *shift < i64::BITS)
+                .ok_or(RuntimeError::InvalidShift)?;
+
+            match operator {
+                TokenType::LessLess => left << shift,
+                _ => left >> shift,
+            }
+        }
+        _ => unreachable!("not a bitwise operator"),
+    };
+
+    Ok(value as f64)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::errors::LoxError;
+    use crate::lox::output::Capture;
     use crate::lox::parser::Parser;
     use crate::lox::resolver::Resolver;
     use crate::lox::scanner::Scanner;
@@ -635,9 +1535,9 @@ mod tests {
                     let mut interpreter = Interpreter::default();
                     interpreter.evaluate(expr.clone())
                 }
-                Stmt::Print(expr) => {
+                Stmt::Print(print) => {
                     let mut interpreter = Interpreter::default();
-                    interpreter.evaluate(expr.clone())
+                    interpreter.evaluate(print.value.clone())
                 }
                 _ => Err(RuntimeError::InvalidBinaryOperands.at(0)),
             }
@@ -676,6 +1576,59 @@ mod tests {
         assert_eq!(res, LiteralExpr::Number(7.0));
     }
 
+    #[test]
+    fn test_integer_and_bitwise_operators() {
+        let cases = [
+            ("7 ~/ 2;", 3.0),
+            ("-7 ~/ 2;", -4.0),
+            ("2 ** 3 ** 2;", 512.0),
+            ("-2 ** 2;", -4.0),
+            ("6 & 3 | 8;", 10.0),
+            ("6 ^ 3;", 5.0),
+            ("~5;", -6.0),
+            ("1 << 4 >> 2;", 4.0),
+            ("1 + 1 << 2;", 8.0),
+        ];
+
+        for (src, expected) in cases {
+            let res = eval_expr(src).expect("evaluation failed");
+            assert_eq!(res, LiteralExpr::Number(expected), "{src}");
+        }
+
+        let err = |src: &str| match eval_expr(src) {
+            Err(LoxError::Runtime(err)) => err.error,
+            other => panic!("expected a runtime error, got {other:?}"),
+        };
+        assert_eq!(err("1.5 | 1;"), RuntimeError::IntegerExpected);
+        assert_eq!(err("~0.5;"), RuntimeError::IntegerExpected);
+        assert_eq!(err("1 << 64;"), RuntimeError::InvalidShift);
+        assert_eq!(err("1 ~/ 0;"), RuntimeError::DivisionByZero);
+    }
+
+    /// Objects a loop running `body` 100 times leaves on the heap, compared to running it 10 times
+    fn heap_growth(setup: &str, body: &str) -> usize {
+        let heap_after = |times: usize| {
+            let src = format!("{setup} var i = 0; while (i < {times}) {{ {body} i = i + 1; }}");
+            exec_src(&src).expect("run failed").heap.len()
+        };
+
+        heap_after(100) - heap_after(10)
+    }
+
+    #[test]
+    fn test_builtin_method_calls_dont_allocate() {
+        assert_eq!(heap_growth("var xs = [];", "xs.push(i); xs.len();"), 0);
+        assert_eq!(heap_growth("var m = {};", "m.has(\"a\"); m.len();"), 0);
+        assert_eq!(
+            heap_growth(
+                "fun count() { var n = 0; while (true) { yield n; n = n + 1; } } var g = count();",
+                "g.hasNext(); g.next();",
+            ),
+            0
+        );
+        assert_eq!(heap_growth("var s = \"ab\";", "s.len(); s.upper();"), 0);
+    }
+
     #[test]
     fn test_division_by_zero_returns_runtime_error() {
         let res = eval_expr("10 / 0;");
@@ -701,8 +1654,18 @@ mod tests {
 
     #[test]
     fn test_print_statement() {
-        let res = run_src("print \"Hello, World! from tests\";");
+        let mut scanner = Scanner::new("print \"Hello, World! from tests\";".to_string());
+        let stmts = Parser::new(scanner.scan_tokens().clone())
+            .parse()
+            .expect("parse failed");
+
+        let out = Capture::new();
+        let mut interpreter = Interpreter::new();
+        interpreter.set_output(out.clone());
+
+        let res = interpreter.interpret(stmts);
         assert!(res.is_ok(), "Print statement should execute successfully");
+        assert_eq!(out.contents(), "Hello, World! from tests\n");
     }
 
     #[test]
@@ -1115,4 +2078,327 @@ mod tests {
             LiteralExpr::String("Fry until golden brown. Pipe full of custard.".to_string())
         );
     }
+
+    #[test]
+    fn test_string_interpolation() {
+        let src = "
+            fun square(n) { return n * n; }
+            class Point {}
+            var n = 4;
+            var res = \"${n} squared is ${square(n)}, ${nil} ${[1, \"a\"]} ${ {\"k\": \"${n}\"}[\"k\"] } ${Point()}\";
+        ";
+        let interpreter = exec_src(src).expect("execution failed");
+        let token = Token::new(TokenType::Identifier, "res".to_string(), 1);
+        let val = interpreter.env.get(&token).expect("variable lookup failed");
+        assert_eq!(
+            val,
+            LiteralExpr::String("4 squared is 16, nil [1, \"a\"] 4 Point instance".to_string())
+        );
+    }
+
+    #[test]
+    fn test_lambdas() {
+        let src = "
+            fun apply(f, x) { return f(x); }
+            fun makeCounter() {
+                var i = 0;
+                return () => { i = i + 1; return i; };
+            }
+            var counter = makeCounter();
+            counter();
+            var res = [
+                apply(fun (n) { return n * 2; }, 21),
+                apply((n) => n + 1, 1),
+                ((a) => (b) => a * b)(6)(7),
+                counter(),
+                (1 + 2),
+            ];
+        ";
+        let interpreter = exec_src(src).expect("execution failed");
+        let token = Token::new(TokenType::Identifier, "res".to_string(), 1);
+        let val = interpreter.env.get(&token).expect("variable lookup failed");
+        let val = interpreter.stringify(&val);
+        assert_eq!(val, "[42, 2, 42, 2, 3]");
+    }
+
+    #[test]
+    fn test_class_members() {
+        let src = "
+            class Circle {
+                class var count = 0;
+                class var unit = Circle(1);
+                init(r) {
+                    this.r = r;
+                    Circle.count = Circle.count + 1;
+                }
+                diameter { return this.r * 2; }
+                class make(r) { return Circle(r); }
+            }
+            class Ring < Circle {}
+
+            var res = [
+                Circle.make(2).diameter,
+                Ring.make(3).r,
+                Ring(4).diameter,
+                Circle.unit.r,
+                Circle.count,
+            ];
+        ";
+        let interpreter = exec_src(src).expect("execution failed");
+        let token = Token::new(TokenType::Identifier, "res".to_string(), 1);
+        let val = interpreter.env.get(&token).expect("variable lookup failed");
+        let val = interpreter.stringify(&val);
+        assert_eq!(val, "[4, 3, 8, 1, 4]");
+    }
+
+    #[test]
+    fn test_try_catch_finally() {
+        let src = "
+            var log = [];
+            fun risky(n) {
+                if (n > 2) throw \"too big\";
+                return n;
+            }
+            fun early() {
+                try { return risky(1); } finally { log.push(\"cleanup\"); }
+            }
+
+            try { risky(5); log.push(\"unreached\"); }
+            catch (e) { log.push(e); }
+            finally { log.push(\"finally\"); }
+
+            try { 1 / 0; } catch (e) { log.push(e.message); log.push(e.line); }
+            log.push(early());
+        ";
+        let interpreter = exec_src(src).expect("execution failed");
+        let token = Token::new(TokenType::Identifier, "log".to_string(), 1);
+        let val = interpreter.env.get(&token).expect("variable lookup failed");
+        let val = interpreter.stringify(&val);
+        assert_eq!(
+            val,
+            "[\"too big\", \"finally\", \"Division by zero.\", 15, \"cleanup\", 1]"
+        );
+
+        let err = exec_src("throw 1 + 2;").unwrap_err();
+        assert_eq!(err.to_string(), "RUNTIME | [line 1] Uncaught exception: 3");
+    }
+
+    #[test]
+    fn test_generators() {
+        let src = "
+            fun numbers(n) {
+                var i = 0;
+                while (i < n) {
+                    try {
+                        if (i == 1) throw i;
+                        yield i;
+                    } catch (e) {
+                        yield \"caught ${e}\";
+                    }
+                    i = i + 1;
+                }
+                return \"end\";
+            }
+
+            var gen = numbers(3);
+            var res = [gen.done];
+            while (!gen.done) res.push(gen.next());
+            res.push(gen.next());
+        ";
+        let interpreter = exec_src(src).expect("execution failed");
+        let token = Token::new(TokenType::Identifier, "res".to_string(), 1);
+        let val = interpreter.env.get(&token).expect("variable lookup failed");
+        let val = interpreter.stringify(&val);
+        assert_eq!(val, "[false, 0, \"caught 1\", 2, \"end\", nil]");
+    }
+
+    #[test]
+    fn test_for_in() {
+        let src = "
+            class Countdown {
+                init(n) { this.n = n; }
+                iterator() { return this; }
+                hasNext() { return this.n > 0; }
+                next() {
+                    this.n = this.n - 1;
+                    return this.n + 1;
+                }
+            }
+
+            fun pairs(xs) {
+                for (var x in xs) yield \"${x}${x}\";
+                return \"end\";
+            }
+
+            var res = [];
+            for (var c in \"ab\") res.push(c);
+            for (var x in [1, 2]) res.push(x);
+            for (var k in {\"k\": 0}) res.push(k);
+            for (var i in range(4, 0, -2)) res.push(i);
+            for (var n in Countdown(2)) res.push(n);
+            for (var p in pairs(range(0, 2))) res.push(p);
+        ";
+        let interpreter = exec_src(src).expect("execution failed");
+        let token = Token::new(TokenType::Identifier, "res".to_string(), 1);
+        let val = interpreter.env.get(&token).expect("variable lookup failed");
+        let val = interpreter.stringify(&val);
+        assert_eq!(
+            val,
+            "[\"a\", \"b\", 1, 2, \"k\", 4, 2, 2, 1, \"00\", \"11\"]"
+        );
+
+        assert!(exec_src("for (var x in 1) print x;").is_err());
+
+        // `in` is only a keyword inside `for`, it's still a valid name
+        let interpreter = exec_src(
+            "
+            var in = [1];
+            fun f(in) { return in; }
+            var total = 0;
+            for (var in in in) total = total + f(in);
+            for (var i = 0; i < 1; i = i + 1) total = total + in[0];
+            ",
+        )
+        .expect("execution failed");
+        let token = Token::new(TokenType::Identifier, "total".to_string(), 1);
+        assert_eq!(
+            interpreter.env.get(&token).unwrap(),
+            LiteralExpr::Number(2.0)
+        );
+        assert!(exec_src("range(0, 1, 0);").is_err());
+    }
+
+    #[test]
+    fn test_operator_overloading() {
+        let src = "
+            class Money {
+                init(cents) { this.cents = cents; }
+                __add__(other) { return Money(this.cents + other.cents); }
+                __neg__() { return Money(-this.cents); }
+                __eq__(other) { return this.cents == other.cents; }
+                __lt__(other) { return this.cents < other.cents; }
+                __index__(i) { return this.cents; }
+                __str__() { return \"$${this.cents}\"; }
+            }
+            class Plain {}
+
+            var plain = Plain();
+            var sum = Money(1) + Money(2);
+            var res = [
+                \"${sum}\", \"${-sum}\", sum == Money(3), sum != Money(3), sum < Money(1), sum[0],
+                plain == plain, plain == Plain(), nil == nil, \"a\" != \"b\"
+            ];
+        ";
+        let interpreter = exec_src(src).expect("execution failed");
+        let token = Token::new(TokenType::Identifier, "res".to_string(), 1);
+        let val = interpreter.env.get(&token).expect("variable lookup failed");
+        let val = interpreter.stringify(&val);
+        assert_eq!(
+            val,
+            "[\"$3\", \"$-3\", true, false, false, 3, true, false, true, true]"
+        );
+
+        assert!(exec_src("class A {} A() + 1;").is_err());
+        // Only `__add__` is defined, `1 + A()` needs `__radd__`
+        assert!(exec_src("class A { __add__(n) { return n; } } 1 + A();").is_err());
+    }
+
+    #[test]
+    fn test_reflected_operators() {
+        let src = "
+            class Meters {
+                init(n) { this.n = n; }
+                __eq__(other) { return this.n == other; }
+                __gt__(other) { return this.n > other; }
+                __rmul__(k) { return Meters(k * this.n); }
+                __rsub__(k) { return Meters(k - this.n); }
+            }
+
+            var m = Meters(2);
+            var res = [
+                m == 2, 2 == m, 2 != m, 3 == m, 1 < m, 3 < m,
+                (3 * m).n, (10 - m).n
+            ];
+        ";
+        let interpreter = exec_src(src).expect("execution failed");
+        let token = Token::new(TokenType::Identifier, "res".to_string(), 1);
+        let val = interpreter.env.get(&token).expect("variable lookup failed");
+        let val = interpreter.stringify(&val);
+        assert_eq!(val, "[true, true, false, false, true, false, 6, 8]");
+    }
+
+    #[test]
+    fn test_to_string() {
+        let src = "
+            fun add(a, b) { return a + b; }
+            class Point {
+                init(x) { this.x = x; }
+                toString() { return \"Point(${this.x})\"; }
+            }
+            class Plain {}
+
+            var res = [
+                \"${add}\", \"${clock}\", \"${Plain}\", \"${Plain()}\", \"${Point(1)}\",
+                \"at \" + Point(2), Plain() + \"!\", [add, Plain()]
+            ];
+        ";
+        let interpreter = exec_src(src).expect("execution failed");
+        let token = Token::new(TokenType::Identifier, "res".to_string(), 1);
+        let val = interpreter.env.get(&token).expect("variable lookup failed");
+        let val = interpreter.stringify(&val);
+        assert_eq!(
+            val,
+            "[\"<fn add>\", \"<native fn clock>\", \"<class Plain>\", \"Plain instance\", \
+             \"Point(1)\", \"at Point(2)\", \"Plain instance!\", [<fn add>, Plain instance]]"
+        );
+    }
+
+    #[test]
+    fn test_to_string_errors() {
+        let err = |src: &str| match exec_src(src) {
+            Err(LoxError::Runtime(err)) => (err.error, err.line),
+            other => panic!("expected a runtime error, got {other:?}"),
+        };
+
+        let number = "class A { toString() { return 1; } }\n\nprint A();";
+        assert_eq!(
+            err(number),
+            (RuntimeError::NotAString("toString".to_string()), 3)
+        );
+
+        let arity = "class A { __str__(x) { return x; } }\nvar a = A();\n\"${a}\";";
+        assert_eq!(
+            err(arity),
+            (RuntimeError::ArgumentCountMismatch(Arity::Exact(1), 0), 3)
+        );
+    }
+
+    #[test]
+    fn test_reflection() {
+        let src = "
+            class Shape { init() { this.sides = 0; } area() { return 0; } }
+            class Square < Shape { area() { return 1; } }
+
+            var sq = Square();
+            setField(sq, \"side\", 2);
+            var res = [
+                typeOf(sq), typeOf(Square), typeOf(clock), typeOf(nil), classOf(sq) == Square,
+                superclassOf(Square) == Shape, superclassOf(Shape), isInstance(sq, Shape),
+                isInstance(Shape(), Square), hasField(sq, \"side\"), hasField(sq, \"area\"),
+                getField(sq, \"side\"), getField(sq, \"area\")(), fields(sq), methods(Square)
+            ];
+        ";
+        let interpreter = exec_src(src).expect("execution failed");
+        let token = Token::new(TokenType::Identifier, "res".to_string(), 1);
+        let val = interpreter.env.get(&token).expect("variable lookup failed");
+        let val = interpreter.stringify(&val);
+        assert_eq!(
+            val,
+            "[\"instance\", \"class\", \"function\", \"nil\", true, true, nil, true, false, \
+             true, false, 2, 1, [\"side\", \"sides\"], [\"area\", \"init\"]]"
+        );
+
+        assert!(exec_src("classOf(1);").is_err());
+        assert!(exec_src("class A {} getField(A(), \"missing\");").is_err());
+    }
 }
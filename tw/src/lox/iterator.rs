@@ -0,0 +1,134 @@
+use std::{cell::Cell, collections::HashMap, rc::Rc};
+
+use crate::{
+    errors::{Locate, LoxError, RuntimeError},
+    lox::{
+        ast::{Arity, LiteralExpr, NativeFn, Object},
+        interpreter::Interpreter,
+        native::number,
+        token::{Token, TokenType},
+    },
+};
+
+/**
+ * `for (var x in seq)` asks `seq` for an iterator, then calls its `hasNext()` and `next()` until
+ * the first one returns false. Strings, lists and maps get a native iterator over their characters,
+ * elements and keys, taken when the loop starts. Generators are their own iterator, and instances
+ * provide one from their `iterator()` method.
+ */
+pub fn iterator(
+    inter: &mut Interpreter,
+    value: LiteralExpr,
+    line: usize,
+) -> Result<LiteralExpr, LoxError> {
+    let items = match value {
+        LiteralExpr::String(s) => s
+            .chars()
+            .map(|c| LiteralExpr::String(c.to_string()))
+            .collect(),
+        LiteralExpr::List(list_id) => match &inter.heap[list_id] {
+            Object::List(items) => items.clone(),
+            _ => return Err(RuntimeError::NotIterable.at(line)),
+        },
+        LiteralExpr::Map(map_id) => match &inter.heap[map_id] {
+            Object::Map(map) => map.entries().map(|(key, _)| key.to_value()).collect(),
+            _ => return Err(RuntimeError::NotIterable.at(line)),
+        },
+        LiteralExpr::Generator(_) => return Ok(value),
+        LiteralExpr::Instance(obj_id) => {
+            let Object::Instance(instance) = inter.heap[obj_id].clone() else {
+                return Err(RuntimeError::NotIterable.at(line));
+            };
+
+            let name = Token::new(TokenType::Identifier, "iterator".to_string(), line);
+            let method = instance
+                .get(&name, inter)
+                .map_err(|_| RuntimeError::NotIterable.at(line))?;
+
+            return inter.call_value(method, Vec::new(), line);
+        }
+        _ => return Err(RuntimeError::NotIterable.at(line)),
+    };
+
+    Ok(sequence(inter, items))
+}
+
+/// Iterator over values known in advance
+fn sequence(inter: &mut Interpreter, items: Vec<LiteralExpr>) -> LiteralExpr {
+    let items = Rc::new(items);
+    let position = Rc::new(Cell::new(0));
+
+    let (left, at) = (items.clone(), position.clone());
+    let has_next = NativeFn::new("hasNext", Arity::Exact(0), move |_, _| {
+        Ok(LiteralExpr::Boolean(at.get() < left.len()))
+    });
+
+    let next = NativeFn::new("next", Arity::Exact(0), move |_, _| {
+        let item = items.get(position.get()).cloned();
+        position.set(position.get() + 1);
+
+        Ok(item.unwrap_or(LiteralExpr::Nil))
+    });
+
+    let fields = HashMap::from([
+        ("hasNext".to_string(), inter.alloc(has_next.into())),
+        ("next".to_string(), inter.alloc(next.into())),
+    ]);
+    inter.namespace_object("Iterator", fields)
+}
+
+/**
+ * `range(start, end, step)` counts from `start` up to `end` excluded, or down to it when `step` is
+ * negative. The range itself only holds the bounds, each `iterator()` call starts a new count.
+ */
+pub fn range_native() -> NativeFn {
+    NativeFn::new("range", Arity::Range(2, 3), |inter, args| {
+        let mut bounds = [0.0, 0.0, 1.0];
+        for (bound, arg) in bounds.iter_mut().zip(&args) {
+            *bound = number(arg)?;
+        }
+
+        let [start, end, step] = bounds;
+        if step == 0.0 {
+            return Err(RuntimeError::ZeroStep.at(0));
+        }
+
+        let iterator = NativeFn::new("iterator", Arity::Exact(0), move |inter, _| {
+            Ok(count(inter, start, end, step))
+        });
+
+        let fields = HashMap::from([
+            ("start".to_string(), LiteralExpr::Number(start)),
+            ("end".to_string(), LiteralExpr::Number(end)),
+            ("step".to_string(), LiteralExpr::Number(step)),
+            ("iterator".to_string(), inter.alloc(iterator.into())),
+        ]);
+        Ok(inter.namespace_object("Range", fields))
+    })
+}
+
+fn count(inter: &mut Interpreter, start: f64, end: f64, step: f64) -> LiteralExpr {
+    let current = Rc::new(Cell::new(start));
+    let in_range = move |n: f64| if step > 0.0 { n < end } else { n > end };
+
+    let at = current.clone();
+    let has_next = NativeFn::new("hasNext", Arity::Exact(0), move |_, _| {
+        Ok(LiteralExpr::Boolean(in_range(at.get())))
+    });
+
+    let next = NativeFn::new("next", Arity::Exact(0), move |_, _| {
+        let n = current.get();
+        if !in_range(n) {
+            return Ok(LiteralExpr::Nil);
+        }
+        current.set(n + step);
+
+        Ok(LiteralExpr::Number(n))
+    });
+
+    let fields = HashMap::from([
+        ("hasNext".to_string(), inter.alloc(has_next.into())),
+        ("next".to_string(), inter.alloc(next.into())),
+    ]);
+    inter.namespace_object("Iterator", fields)
+}
@@ -0,0 +1,183 @@
+use crate::{
+    errors::{Locate, LoxError, RuntimeError},
+    lox::{
+        ast::{Arity, LiteralExpr, NativeFn, Object},
+        interpreter::Interpreter,
+        native::integer,
+    },
+};
+
+/// Position of `index` in a list of `len` elements, negative indices count from the end
+pub fn list_index(index: &LiteralExpr, len: usize) -> Result<usize, RuntimeError> {
+    let position = integer(index).map_err(|_| RuntimeError::InvalidIndex)?;
+    let resolved = if position < 0 {
+        len as i64 + position
+    } else {
+        position
+    };
+
+    if resolved < 0 || resolved >= len as i64 {
+        return Err(RuntimeError::IndexOutOfRange(position as f64, len));
+    }
+
+    Ok(resolved as usize)
+}
+
+/**
+ * List methods are natives bound to the list they were read from, they capture the heap id of the
+ * list. `xs.push(x)` calls one right away, only reading `xs.push` alone puts it on the heap.
+ *
+ * Their errors have no line (natives don't know where they are called from), the interpreter fills
+ * it with the line of the call.
+ */
+pub fn list_method(list_id: usize, name: &str) -> Option<NativeFn> {
+    let method = match name {
+        "len" => NativeFn::new("len", Arity::Exact(0), move |inter, _| {
+            let len = items(inter, list_id)?.len();
+
+            Ok(LiteralExpr::Number(len as f64))
+        }),
+        "push" => NativeFn::new("push", Arity::Exact(1), move |inter, mut args| {
+            items(inter, list_id)?.push(args.remove(0));
+
+            Ok(LiteralExpr::Nil)
+        }),
+        "pop" => NativeFn::new("pop", Arity::Exact(0), move |inter, _| {
+            items(inter, list_id)?
+                .pop()
+                .ok_or_else(|| RuntimeError::EmptyList.at(0))
+        }),
+        "insert" => NativeFn::new("insert", Arity::Exact(2), move |inter, mut args| {
+            let value = args.remove(1);
+            let list = items(inter, list_id)?;
+
+            let position = match list_index(&args[0], list.len()) {
+                Ok(position) => position,
+                // Inserting right after the last element appends it
+                Err(_) if args[0] == LiteralExpr::Number(list.len() as f64) => list.len(),
+                Err(err) => return Err(err.at(0)),
+            };
+            list.insert(position, value);
+
+            Ok(LiteralExpr::Nil)
+        }),
+        "remove" => NativeFn::new("remove", Arity::Exact(1), move |inter, args| {
+            let list = items(inter, list_id)?;
+            let position = list_index(&args[0], list.len()).map_err(|e| e.at(0))?;
+
+            Ok(list.remove(position))
+        }),
+        "slice" => NativeFn::new("slice", Arity::Range(1, 2), move |inter, args| {
+            let list = items(inter, list_id)?;
+            let len = list.len();
+
+            let start = slice_bound(&args[0], len)?;
+            let end = match args.get(1) {
+                Some(end) => slice_bound(end, len)?,
+                None => len,
+            };
+
+            let slice = list[start..end.max(start)].to_vec();
+            Ok(inter.alloc(Object::List(slice)))
+        }),
+        _ => return None,
+    };
+
+    Some(method)
+}
+
+fn items(inter: &mut Interpreter, list_id: usize) -> Result<&mut Vec<LiteralExpr>, LoxError> {
+    match &mut inter.heap[list_id] {
+        Object::List(items) => Ok(items),
+        _ => Err(RuntimeError::NotIndexable.at(0)),
+    }
+}
+
+/// Slice bounds are clamped to the list instead of failing, like in most scripting languages
+pub fn slice_bound(bound: &LiteralExpr, len: usize) -> Result<usize, LoxError> {
+    let position = integer(bound).map_err(|_| RuntimeError::InvalidIndex.at(0))?;
+
+    let resolved = if position < 0 {
+        len as i64 + position
+    } else {
+        position
+    };
+
+    Ok(resolved.clamp(0, len as i64) as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        errors::{LoxError, RuntimeError},
+        lox::{
+            Value,
+            test_util::{run, runtime_error},
+        },
+    };
+
+    #[test]
+    fn test_list_literal_and_indexing() {
+        let (res, out) = run("var xs = [1, \"two\", [3]]; print xs; print xs[1]; print xs[-1][0];");
+
+        assert!(res.is_ok());
+        assert_eq!(out, "[1, \"two\", [3]]\ntwo\n3\n");
+    }
+
+    #[test]
+    fn test_lists_are_shared_by_reference() {
+        let (res, _) = run("var a = [1, 2]; var b = a; b[0] = 10; b.push(3); a[0] + a.len();");
+
+        assert_eq!(res.unwrap(), Value::Number(13.0));
+    }
+
+    #[test]
+    fn test_list_methods() {
+        let (res, out) = run("
+            var xs = [];
+            xs.push(1); xs.push(2); xs.push(3);
+            print xs.pop();
+            xs.insert(0, 0);
+            xs.insert(-1, 5);
+            print xs;
+            print xs.remove(1);
+            print xs.slice(1);
+            print xs.slice(-2, 10);
+            print xs.slice(2, 1);
+        ");
+
+        assert!(res.is_ok(), "{res:?}");
+        assert_eq!(out, "3\n[0, 1, 5, 2]\n1\n[5, 2]\n[5, 2]\n[]\n");
+    }
+
+    #[test]
+    fn test_list_errors() {
+        assert_eq!(
+            runtime_error("[1, 2][2];"),
+            RuntimeError::IndexOutOfRange(2.0, 2)
+        );
+        assert_eq!(
+            runtime_error("[1, 2][-3] = 0;"),
+            RuntimeError::IndexOutOfRange(-3.0, 2)
+        );
+        assert_eq!(runtime_error("[1][0.5];"), RuntimeError::InvalidIndex);
+        assert_eq!(
+            runtime_error("var s = \"ab\"; s[0];"),
+            RuntimeError::NotIndexable
+        );
+        assert_eq!(runtime_error("[].pop();"), RuntimeError::EmptyList);
+        assert_eq!(
+            runtime_error("[].nope();"),
+            RuntimeError::UndefinedProperty("nope".to_string())
+        );
+    }
+
+    #[test]
+    fn test_method_errors_take_the_call_line() {
+        let Err(LoxError::Runtime(err)) = run("var xs = [];\n\nxs.remove(0);").0 else {
+            panic!("expected a runtime error");
+        };
+
+        assert_eq!(err.line, 3);
+    }
+}
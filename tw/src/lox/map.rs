@@ -0,0 +1,220 @@
+use std::collections::HashMap;
+
+use crate::{
+    errors::{Locate, LoxError, RuntimeError},
+    lox::{
+        ast::{Arity, LiteralExpr, NativeFn, Object},
+        interpreter::Interpreter,
+    },
+};
+
+/**
+ * Only values compared by value can be keys, so a key never changes while it's in a map.
+ *
+ * Numbers are stored by their bits after turning `-0` into `0`, which keeps `m[0]` and `m[-0]` the same
+ * entry. `NaN` is rejected because it's not equal to itself, a `NaN` key could never be read back.
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum MapKey {
+    Nil,
+    Boolean(bool),
+    Number(u64),
+    String(String),
+}
+
+/// Map that remembers insertion order, it's the order of `keys()`, `values()` and printing
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct LoxMap {
+    entries: Vec<(MapKey, LiteralExpr)>,
+    positions: HashMap<MapKey, usize>,
+}
+
+impl MapKey {
+    pub fn from_value(value: &LiteralExpr) -> Result<Self, RuntimeError> {
+        match value {
+            LiteralExpr::Nil => Ok(MapKey::Nil),
+            LiteralExpr::Boolean(b) => Ok(MapKey::Boolean(*b)),
+            LiteralExpr::Number(n) if n.is_nan() => Err(RuntimeError::InvalidMapKey),
+            LiteralExpr::Number(n) if *n == 0.0 => Ok(MapKey::Number(0f64.to_bits())),
+            LiteralExpr::Number(n) => Ok(MapKey::Number(n.to_bits())),
+            LiteralExpr::String(s) => Ok(MapKey::String(s.clone())),
+            _ => Err(RuntimeError::InvalidMapKey),
+        }
+    }
+
+    pub fn to_value(&self) -> LiteralExpr {
+        match self {
+            MapKey::Nil => LiteralExpr::Nil,
+            MapKey::Boolean(b) => LiteralExpr::Boolean(*b),
+            MapKey::Number(bits) => LiteralExpr::Number(f64::from_bits(*bits)),
+            MapKey::String(s) => LiteralExpr::String(s.clone()),
+        }
+    }
+}
+
+impl LoxMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get(&self, key: &MapKey) -> Option<&LiteralExpr> {
+        self.positions.get(key).map(|i| &self.entries[*i].1)
+    }
+
+    /// Replaces the value of an existing key in place, new keys go at the end
+    pub fn insert(&mut self, key: MapKey, value: LiteralExpr) {
+        if let Some(i) = self.positions.get(&key) {
+            self.entries[*i].1 = value;
+            return;
+        }
+
+        self.positions.insert(key.clone(), self.entries.len());
+        self.entries.push((key, value));
+    }
+
+    pub fn remove(&mut self, key: &MapKey) -> Option<LiteralExpr> {
+        let i = self.positions.remove(key)?;
+        let (_, value) = self.entries.remove(i);
+
+        // Every entry after the removed one moved one place to the left
+        for position in self.positions.values_mut() {
+            if *position > i {
+                *position -= 1;
+            }
+        }
+
+        Some(value)
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = &(MapKey, LiteralExpr)> {
+        self.entries.iter()
+    }
+}
+
+/// Map methods are bound natives, same as the ones of lists (see `list_method`)
+pub fn map_method(map_id: usize, name: &str) -> Option<NativeFn> {
+    let method = match name {
+        "len" => NativeFn::new("len", Arity::Exact(0), move |inter, _| {
+            let len = map(inter, map_id)?.len();
+
+            Ok(LiteralExpr::Number(len as f64))
+        }),
+        "has" => NativeFn::new("has", Arity::Exact(1), move |inter, args| {
+            let key = MapKey::from_value(&args[0]).map_err(|e| e.at(0))?;
+
+            Ok(LiteralExpr::Boolean(
+                map(inter, map_id)?.get(&key).is_some(),
+            ))
+        }),
+        "remove" => NativeFn::new("remove", Arity::Exact(1), move |inter, args| {
+            let key = MapKey::from_value(&args[0]).map_err(|e| e.at(0))?;
+
+            map(inter, map_id)?
+                .remove(&key)
+                .ok_or_else(|| RuntimeError::UndefinedKey(args[0].to_string()).at(0))
+        }),
+        "keys" => NativeFn::new("keys", Arity::Exact(0), move |inter, _| {
+            let keys = map(inter, map_id)?
+                .entries()
+                .map(|(key, _)| key.to_value())
+                .collect();
+
+            Ok(inter.alloc(Object::List(keys)))
+        }),
+        "values" => NativeFn::new("values", Arity::Exact(0), move |inter, _| {
+            let values = map(inter, map_id)?
+                .entries()
+                .map(|(_, value)| value.clone())
+                .collect();
+
+            Ok(inter.alloc(Object::List(values)))
+        }),
+        _ => return None,
+    };
+
+    Some(method)
+}
+
+fn map(inter: &mut Interpreter, map_id: usize) -> Result<&mut LoxMap, LoxError> {
+    match &mut inter.heap[map_id] {
+        Object::Map(map) => Ok(map),
+        _ => Err(RuntimeError::NotIndexable.at(0)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lox::{
+        Value,
+        test_util::{run, runtime_error},
+    };
+
+    #[test]
+    fn test_map_literal_and_subscript() {
+        let (res, out) = run("
+            var m = {\"b\": 1, 2: true, nil: [1]};
+            m[\"a\"] = 3;
+            m[\"b\"] = 10;
+            print m;
+            print m[2];
+            print {};
+        ");
+
+        assert!(res.is_ok(), "{res:?}");
+        assert_eq!(out, "{\"b\": 10, 2: true, nil: [1], \"a\": 3}\ntrue\n{}\n");
+    }
+
+    #[test]
+    fn test_map_methods_keep_insertion_order() {
+        let (res, out) = run("
+            var m = {\"x\": 1, \"y\": 2, \"z\": 3};
+            print m.remove(\"x\");
+            m[\"x\"] = 4;
+            print m.keys();
+            print m.values();
+            print m.has(\"y\");
+            print m.has(\"w\");
+            print m.len();
+        ");
+
+        assert!(res.is_ok(), "{res:?}");
+        assert_eq!(out, "1\n[\"y\", \"z\", \"x\"]\n[2, 3, 4]\ntrue\nfalse\n3\n");
+    }
+
+    #[test]
+    fn test_map_key_equality() {
+        let (res, _) =
+            run("var m = {0: \"zero\", \"1\": \"str\"}; m[1] = \"num\"; m[-0] + m[\"1\"] + m[1];");
+
+        assert_eq!(res.unwrap(), Value::String("zerostrnum".to_string()));
+        assert_eq!(
+            MapKey::from_value(&LiteralExpr::Number(f64::NAN)),
+            Err(RuntimeError::InvalidMapKey)
+        );
+    }
+
+    #[test]
+    fn test_map_errors() {
+        assert_eq!(
+            runtime_error("var m = {\"a\": 1}; m[\"b\"];"),
+            RuntimeError::UndefinedKey("\"b\"".to_string())
+        );
+        assert_eq!(
+            runtime_error("var m = {}; m[[]] = 1;"),
+            RuntimeError::InvalidMapKey
+        );
+        assert_eq!(
+            runtime_error("var m = {}; m.remove(1);"),
+            RuntimeError::UndefinedKey("1".to_string())
+        );
+    }
+}
@@ -0,0 +1,232 @@
+use std::{
+    cell::Cell,
+    f64::consts::PI,
+    rc::Rc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    errors::{Locate, LoxError, RuntimeError},
+    lox::{
+        ast::{Arity, LiteralExpr, NativeFn},
+        native::{NativeModule, integer, number},
+    },
+};
+
+/**
+ * Numeric natives under the `math` namespace.
+ *
+ * `random` and `randomInt` share one generator per interpreter. It starts from the clock, so runs
+ * differ until `math.seed(n)` is called: after it, the same seed always gives the same numbers.
+ */
+pub struct MathModule;
+
+impl NativeModule for MathModule {
+    fn namespace(&self) -> Option<&str> {
+        Some("math")
+    }
+
+    fn constants(&self) -> Vec<(&str, LiteralExpr)> {
+        vec![
+            ("PI", LiteralExpr::Number(PI)),
+            ("INF", LiteralExpr::Number(f64::INFINITY)),
+        ]
+    }
+
+    fn natives(&self) -> Vec<NativeFn> {
+        let rng = Rc::new(SplitMix64::from_clock());
+
+        let mut natives = vec![
+            unary("abs", f64::abs),
+            unary("floor", f64::floor),
+            unary("ceil", f64::ceil),
+            unary("round", f64::round),
+            unary("sqrt", f64::sqrt),
+            unary("sin", f64::sin),
+            unary("cos", f64::cos),
+            unary("tan", f64::tan),
+            NativeFn::new("pow", Arity::Exact(2), |_, args| {
+                Ok(LiteralExpr::Number(
+                    number(&args[0])?.powf(number(&args[1])?),
+                ))
+            }),
+            // Natural logarithm, or in the base given as second argument
+            NativeFn::new("log", Arity::Range(1, 2), |_, args| {
+                let x = number(&args[0])?;
+                let log = match args.get(1) {
+                    Some(base) => x.log(number(base)?),
+                    None => x.ln(),
+                };
+
+                Ok(LiteralExpr::Number(log))
+            }),
+            NativeFn::new("min", Arity::Range(1, 255), |_, args| fold(&args, f64::min)),
+            NativeFn::new("max", Arity::Range(1, 255), |_, args| fold(&args, f64::max)),
+            NativeFn::new("isNaN", Arity::Exact(1), |_, args| {
+                Ok(LiteralExpr::Boolean(number(&args[0])?.is_nan()))
+            }),
+        ];
+
+        let seed_rng = rng.clone();
+        natives.push(NativeFn::new("seed", Arity::Exact(1), move |_, args| {
+            seed_rng.seed(number(&args[0])?);
+
+            Ok(LiteralExpr::Nil)
+        }));
+
+        let random_rng = rng.clone();
+        natives.push(NativeFn::new("random", Arity::Exact(0), move |_, _| {
+            Ok(LiteralExpr::Number(random_rng.next_f64()))
+        }));
+
+        // Both bounds are included
+        natives.push(NativeFn::new(
+            "randomInt",
+            Arity::Exact(2),
+            move |_, args| {
+                let (low, high) = (integer(&args[0])?, integer(&args[1])?);
+                if low > high {
+                    return Err(RuntimeError::InvalidBounds(low as f64, high as f64).at(0));
+                }
+
+                let span = high.abs_diff(low) + 1;
+                let offset = rng.next_u64() % span;
+
+                Ok(LiteralExpr::Number(low.wrapping_add_unsigned(offset) as f64))
+            },
+        ));
+
+        natives
+    }
+}
+
+/// SplitMix64, small and fast, and good enough for games and simulations (not for secrets)
+struct SplitMix64 {
+    state: Cell<u64>,
+}
+
+impl SplitMix64 {
+    fn from_clock() -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_nanos() as u64);
+
+        Self {
+            state: Cell::new(nanos),
+        }
+    }
+
+    fn seed(&self, seed: f64) {
+        self.state.set(seed.to_bits());
+    }
+
+    fn next_u64(&self) -> u64 {
+        let state = self.state.get().wrapping_add(0x9E37_79B9_7F4A_7C15);
+        self.state.set(state);
+
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// In `[0, 1)`, from the 53 high bits so every value is exact
+    fn next_f64(&self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+fn unary(name: &str, function: fn(f64) -> f64) -> NativeFn {
+    NativeFn::new(name, Arity::Exact(1), move |_, args| {
+        Ok(LiteralExpr::Number(function(number(&args[0])?)))
+    })
+}
+
+fn fold(args: &[LiteralExpr], function: fn(f64, f64) -> f64) -> Result<LiteralExpr, LoxError> {
+    let mut result = number(&args[0])?;
+    for arg in &args[1..] {
+        result = function(result, number(arg)?);
+    }
+
+    Ok(LiteralExpr::Number(result))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        errors::RuntimeError,
+        lox::{
+            Value,
+            test_util::{run, runtime_error},
+        },
+    };
+
+    #[test]
+    fn test_math_functions() {
+        let (res, out) = run("
+            print [math.abs(-2), math.floor(1.5), math.ceil(1.5), math.round(2.5), math.sqrt(16)];
+            print [math.pow(2, 10), math.round(math.log(8, 2)), math.log(1), math.cos(0)];
+            print [math.min(3, 1, 2), math.max(3, 1, 2), math.floor(math.PI * 100)];
+            print [math.isNaN(math.sqrt(-1)), math.isNaN(1), math.INF > 10 ** 300];
+            ");
+
+        assert!(res.is_ok(), "{res:?}");
+        assert_eq!(
+            out,
+            "[2, 1, 2, 3, 4]\n[1024, 3, 0, 1]\n[1, 3, 314]\n[true, false, true]\n"
+        );
+    }
+
+    #[test]
+    fn test_seeded_random_is_reproducible() {
+        let src = |seed: u32| {
+            format!(
+                "
+            math.seed({seed});
+            var xs = [];
+            var i = 0;
+            while (i < 20) {{
+                xs.push(math.random());
+                xs.push(math.randomInt(-3, 3));
+                i = i + 1;
+            }}
+            str(xs);
+            "
+            )
+        };
+        let eval = |src: &str| run(src).0.expect("eval failed");
+        let first = eval(&src(42));
+        assert_eq!(first, eval(&src(42)));
+        assert_ne!(first, eval(&src(43)));
+
+        let (in_range, _) = run("
+            math.seed(7);
+            var ok = true;
+            var i = 0;
+            while (i < 200) {
+                var r = math.random();
+                var n = math.randomInt(1, 6);
+                ok = ok and r >= 0 and r < 1 and n >= 1 and n <= 6 and n == math.floor(n);
+                i = i + 1;
+            }
+            ok;
+            ");
+        assert_eq!(in_range.unwrap(), Value::Boolean(true));
+    }
+
+    #[test]
+    fn test_math_errors() {
+        assert_eq!(
+            runtime_error("math.sqrt(\"4\");"),
+            RuntimeError::NumberExpected
+        );
+        assert_eq!(
+            runtime_error("math.randomInt(1.5, 2);"),
+            RuntimeError::IntegerExpected
+        );
+        assert_eq!(
+            runtime_error("math.randomInt(3, 1);"),
+            RuntimeError::InvalidBounds(3.0, 1.0)
+        );
+    }
+}
@@ -1,10 +1,30 @@
 pub mod ast;
+mod embed;
 mod env;
+mod fs;
+mod generator;
 mod interpreter;
+mod iterator;
+mod list;
+mod map;
+mod math;
+mod module;
+mod native;
+mod output;
 mod parser;
+mod process;
+mod reflect;
 mod resolver;
 mod run;
 mod scanner;
+mod string;
+#[cfg(test)]
+mod test_util;
 pub mod token;
 
+pub use embed::{Lox, Value};
+pub use fs::Permissions;
+pub use interpreter::Interpreter;
+pub use native::NativeModule;
+pub use output::Capture;
 pub use run::{RunOptsCommand, handle_run_command};
@@ -0,0 +1,250 @@
+use std::{
+    collections::HashMap,
+    env,
+    path::{Path, PathBuf},
+};
+
+use crate::lox::{ast::LiteralExpr, env::EnvId};
+
+/// A module that already ran, its globals live in the `env` root node of the environment arena
+#[derive(Debug, Clone)]
+pub struct Module {
+    pub env: EnvId,
+    pub namespace: LiteralExpr,
+}
+
+/**
+ * Bookkeeping for `import`.
+ *
+ * Modules are cached by their canonical path, so a file imported from many places runs only once.
+ * `loading` is the chain of files being executed right now (the main script first, when there is one),
+ * it's used to resolve relative imports and to find cycles before they turn into infinite recursion.
+ */
+#[derive(Debug, Default)]
+pub struct Modules {
+    search_paths: Vec<PathBuf>,
+    loaded: HashMap<PathBuf, Module>,
+    loading: Vec<PathBuf>,
+}
+
+impl Modules {
+    pub fn add_search_path(&mut self, path: impl Into<PathBuf>) {
+        self.search_paths.push(path.into());
+    }
+
+    /// Marks `path` as the file that is running, imports inside it are resolved from its directory
+    pub fn enter(&mut self, path: &Path) {
+        let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+
+        self.loading.push(path);
+    }
+
+    pub fn exit(&mut self) {
+        self.loading.pop();
+    }
+
+    /// Finds `import_path` relative to the importing file (or the working directory), then in the search paths
+    pub fn resolve(&self, import_path: &str) -> Option<PathBuf> {
+        let base = match self.loading.last().and_then(|file| file.parent()) {
+            Some(dir) => Some(dir.to_path_buf()),
+            None => env::current_dir().ok(),
+        };
+
+        base.into_iter()
+            .chain(self.search_paths.iter().cloned())
+            .map(|dir| dir.join(import_path))
+            .find(|path| path.is_file())
+            .and_then(|path| path.canonicalize().ok())
+    }
+
+    pub fn get(&self, path: &Path) -> Option<&Module> {
+        self.loaded.get(path)
+    }
+
+    pub fn insert(&mut self, path: PathBuf, module: Module) {
+        self.loaded.insert(path, module);
+    }
+
+    /// The import chain that leads back to `path`, if it's still running
+    pub fn cycle(&self, path: &Path) -> Option<String> {
+        let start = self.loading.iter().position(|file| file == path)?;
+
+        let chain: Vec<String> = self.loading[start..]
+            .iter()
+            .chain([&path.to_path_buf()])
+            .map(|file| display_path(file))
+            .collect();
+
+        Some(chain.join(" -> "))
+    }
+}
+
+/// Path relative to the working directory when possible, it's shorter to read in error messages
+pub fn display_path(path: &Path) -> String {
+    let relative = env::current_dir()
+        .ok()
+        .and_then(|cwd| path.strip_prefix(cwd).ok().map(Path::to_path_buf));
+
+    relative
+        .unwrap_or_else(|| path.to_path_buf())
+        .to_string_lossy()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::{
+        errors::{LoxError, RuntimeError},
+        lox::{Capture, Lox, Value},
+    };
+
+    /// Writes `files` to a fresh directory and returns an interpreter that imports from it
+    fn setup(test: &str, files: &[(&str, &str)]) -> (Lox, Capture) {
+        let dir = std::env::temp_dir().join(format!("tw-modules-{}-{test}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).expect("failed to create test dir");
+
+        for (name, source) in files {
+            fs::write(dir.join(name), source).expect("failed to write module");
+        }
+
+        let out = Capture::new();
+        let mut lox = Lox::new();
+        lox.add_search_path(&dir);
+        lox.set_output(out.clone());
+
+        (lox, out)
+    }
+
+    #[test]
+    fn test_import_namespace_and_names() {
+        let (mut lox, _) = setup(
+            "names",
+            &[("util.lox", "var base = 10; fun add(n) { return base + n; }")],
+        );
+
+        let val = lox
+            .eval("import \"util.lox\"; util.add(1);")
+            .expect("eval failed");
+        assert_eq!(val, Value::Number(11.0));
+
+        let val = lox
+            .eval("import { add, base } from \"util.lox\"; add(base);")
+            .expect("eval failed");
+        assert_eq!(val, Value::Number(20.0));
+    }
+
+    #[test]
+    fn test_module_runs_once_with_own_globals() {
+        let (mut lox, out) = setup(
+            "once",
+            &[
+                (
+                    "counter.lox",
+                    "print \"loaded\"; var count = 0; fun next() { count = count + 1; return count; }",
+                ),
+                ("a.lox", "import { next } from \"counter.lox\"; next();"),
+            ],
+        );
+
+        let val = lox
+            .eval("import \"a.lox\"; import { next } from \"counter.lox\"; next();")
+            .expect("eval failed");
+
+        assert_eq!(val, Value::Number(2.0));
+        assert_eq!(out.contents(), "loaded\n");
+
+        // `count` belongs to the module, not to the importing script
+        assert!(lox.eval("count;").is_err());
+    }
+
+    #[test]
+    fn test_namespace_sees_module_state() {
+        let (mut lox, out) = setup(
+            "live",
+            &[(
+                "util.lox",
+                "var count = 0; fun bump() { count = count + 1; } fun get() { return count; }",
+            )],
+        );
+
+        let val = lox
+            .eval(
+                "
+                import \"util.lox\";
+                util.bump();
+                util.bump();
+                print util.count;
+                util.count = 10;
+                print hasField(util, \"count\");
+                util.get();
+                ",
+            )
+            .expect("eval failed");
+
+        assert_eq!(out.contents(), "2\ntrue\n");
+        assert_eq!(val, Value::Number(10.0));
+    }
+
+    #[test]
+    fn test_module_cannot_read_importer_globals() {
+        let (mut lox, _) = setup(
+            "isolated",
+            &[(
+                "leak.lox",
+                "fun leak() { return secret; } fun time() { return clock() > 0; }",
+            )],
+        );
+
+        lox.eval("var secret = 42; import { leak, time } from \"leak.lox\";")
+            .expect("eval failed");
+
+        // Natives are shared by every module, the globals of the importer are not
+        assert_eq!(
+            lox.eval("time();").expect("eval failed"),
+            Value::Boolean(true)
+        );
+        let Err(LoxError::Runtime(err)) = lox.eval("leak();") else {
+            panic!("expected an undefined variable error");
+        };
+        assert_eq!(
+            err.error,
+            RuntimeError::UndefinedVariable("secret".to_string())
+        );
+    }
+
+    #[test]
+    fn test_import_errors() {
+        let (mut lox, _) = setup(
+            "errors",
+            &[
+                ("a.lox", "import \"b.lox\";"),
+                ("b.lox", "import \"a.lox\";"),
+                ("bad.lox", "var a = 1;\nprint a / 0;"),
+                ("ok.lox", "var a = 1;"),
+            ],
+        );
+
+        let Err(LoxError::Runtime(err)) = lox.eval("import \"a.lox\";") else {
+            panic!("expected an import cycle error");
+        };
+        assert!(matches!(err.error, RuntimeError::ImportCycle(_)));
+
+        let Err(LoxError::Runtime(err)) = lox.eval("import \"bad.lox\";") else {
+            panic!("expected a runtime error");
+        };
+        assert_eq!(err.line, 2);
+        assert!(err.file.is_some_and(|file| file.ends_with("bad.lox")));
+
+        assert!(matches!(
+            lox.eval("import \"missing.lox\";"),
+            Err(LoxError::Runtime(_))
+        ));
+        let Err(LoxError::Runtime(err)) = lox.eval("import { nope } from \"ok.lox\";") else {
+            panic!("expected an undefined export error");
+        };
+        assert!(matches!(err.error, RuntimeError::UndefinedExport(..)));
+    }
+}
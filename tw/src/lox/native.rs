@@ -0,0 +1,159 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::{
+    errors::{Locate, LoxError, RuntimeError},
+    lox::{
+        ast::{Arity, LiteralExpr, NativeFn},
+        iterator::range_native,
+    },
+};
+
+/**
+ * A group of related native functions registered with a single call to `Interpreter::register_module`.
+ *
+ * Natives are boxed closures, so a module can hand them any state they need (a handle, a config)
+ * by capturing it. When `namespace` returns a name, the functions are reachable as fields of a
+ * global object with that name (e.g. `math.sqrt(2)`) instead of being globals themselves.
+ */
+pub trait NativeModule {
+    fn namespace(&self) -> Option<&str> {
+        None
+    }
+
+    /// Values defined next to the natives, like `math.PI`
+    fn constants(&self) -> Vec<(&str, LiteralExpr)> {
+        Vec::new()
+    }
+
+    fn natives(&self) -> Vec<NativeFn>;
+}
+
+/// Natives available in every interpreter
+pub struct CoreModule;
+
+impl NativeModule for CoreModule {
+    fn natives(&self) -> Vec<NativeFn> {
+        let clock = NativeFn::new("clock", Arity::Exact(0), |_, _| {
+            let time = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs_f64();
+
+            Ok(LiteralExpr::Number(time))
+        });
+
+        vec![clock, range_native()]
+    }
+}
+
+// region: Argument checks
+// Shared by the natives. Like every native error they have no line, the interpreter fills it with
+// the line of the call.
+
+pub fn string(value: &LiteralExpr) -> Result<&str, LoxError> {
+    match value {
+        LiteralExpr::String(s) => Ok(s),
+        _ => Err(RuntimeError::StringExpected.at(0)),
+    }
+}
+
+pub fn number(value: &LiteralExpr) -> Result<f64, LoxError> {
+    match value {
+        LiteralExpr::Number(n) => Ok(*n),
+        _ => Err(RuntimeError::NumberExpected.at(0)),
+    }
+}
+
+pub fn integer(value: &LiteralExpr) -> Result<i64, LoxError> {
+    integral(number(value)?).map_err(|e| e.at(0))
+}
+
+/// The `i64` value of an integral number, bitwise operators check their operands with it too
+pub fn integral(num: f64) -> Result<i64, RuntimeError> {
+    // i64::MAX isn't exactly representable, the bound is the first power of two above it
+    if num.fract() != 0.0 || num.abs() >= 2f64.powi(63) {
+        return Err(RuntimeError::IntegerExpected);
+    }
+
+    Ok(num as i64)
+}
+
+// endregion: Argument checks
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, rc::Rc};
+
+    use super::*;
+    use crate::{errors::LoxError, lox::Lox};
+
+    struct Counter {
+        hits: Rc<Cell<usize>>,
+    }
+
+    impl NativeModule for Counter {
+        fn namespace(&self) -> Option<&str> {
+            Some("counter")
+        }
+
+        fn natives(&self) -> Vec<NativeFn> {
+            let hits = self.hits.clone();
+            let hit = NativeFn::new("hit", Arity::Exact(0), move |_, _| {
+                hits.set(hits.get() + 1);
+                Ok(LiteralExpr::Number(hits.get() as f64))
+            });
+
+            let hits = self.hits.clone();
+            let reset = NativeFn::new("reset", Arity::Exact(0), move |_, _| {
+                hits.set(0);
+                Ok(LiteralExpr::Nil)
+            });
+
+            vec![hit, reset]
+        }
+    }
+
+    #[test]
+    fn test_native_closure_captures_state() {
+        let hits = Rc::new(Cell::new(0));
+        let mut lox = Lox::new();
+        lox.register_module(&Counter { hits: hits.clone() });
+
+        let val = lox
+            .eval("counter.hit(); counter.hit(); counter.hit();")
+            .expect("eval failed");
+
+        assert_eq!(val, LiteralExpr::Number(3.0));
+        assert_eq!(hits.get(), 3);
+
+        lox.eval("counter.reset();").expect("eval failed");
+        assert_eq!(hits.get(), 0);
+    }
+
+    #[test]
+    fn test_native_arity_kinds() {
+        let mut lox = Lox::new();
+        lox.define_native("sum", Arity::Variadic, |_, args| {
+            let mut total = 0.0;
+            for arg in args {
+                if let LiteralExpr::Number(n) = arg {
+                    total += n;
+                }
+            }
+
+            Ok(LiteralExpr::Number(total))
+        });
+        lox.define_native("opt", Arity::Range(1, 2), |_, args| {
+            Ok(LiteralExpr::Number(args.len() as f64))
+        });
+
+        assert_eq!(lox.eval("sum();").unwrap(), LiteralExpr::Number(0.0));
+        assert_eq!(lox.eval("sum(1, 2, 3);").unwrap(), LiteralExpr::Number(6.0));
+        assert_eq!(lox.eval("opt(1, 2);").unwrap(), LiteralExpr::Number(2.0));
+        assert!(matches!(lox.eval("opt();"), Err(LoxError::Runtime(_))));
+        assert!(matches!(
+            lox.eval("opt(1, 2, 3);"),
+            Err(LoxError::Runtime(_))
+        ));
+    }
+}
@@ -0,0 +1,97 @@
+use std::{
+    cell::RefCell,
+    fmt,
+    io::{self, Write},
+    rc::Rc,
+};
+
+/// Where the output of `print` statements goes, stdout unless the host sets another sink
+pub struct Output(Box<dyn Write>);
+
+impl Output {
+    pub fn new(sink: impl Write + 'static) -> Self {
+        Self(Box::new(sink))
+    }
+}
+
+impl Default for Output {
+    fn default() -> Self {
+        Self::new(io::stdout())
+    }
+}
+
+impl fmt::Debug for Output {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Output")
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+/// Where `report` writes errors, stderr unless the host sets another sink
+pub struct ErrorOutput(Output);
+
+impl ErrorOutput {
+    pub fn new(sink: impl Write + 'static) -> Self {
+        Self(Output::new(sink))
+    }
+}
+
+impl Default for ErrorOutput {
+    fn default() -> Self {
+        Self::new(io::stderr())
+    }
+}
+
+impl fmt::Debug for ErrorOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ErrorOutput")
+    }
+}
+
+impl Write for ErrorOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+/// In-memory sink, clones share the same buffer so the host can keep one to read what was written
+#[derive(Debug, Clone, Default)]
+pub struct Capture(Rc<RefCell<Vec<u8>>>);
+
+impl Capture {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.0.borrow()).to_string()
+    }
+
+    pub fn clear(&self) {
+        self.0.borrow_mut().clear();
+    }
+}
+
+impl Write for Capture {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -1,8 +1,12 @@
+use std::path::Path;
+
 use crate::{
     errors::{Locate, LocateResult, LoxError, ParseError, RuntimeError},
     lox::ast::{
-        AssignmentExpr, CallExpr, ClassStmt, FunStmt, GetExpr, IfStmt, LogicalExpr, ReturnStmt,
-        SetExpr, Stmt, SuperExpr, ThisExpr, VarExpr, VarStmt, WhileStmt,
+        AssignmentExpr, CallExpr, CatchClause, ClassStmt, FunStmt, GetExpr, IfStmt, ImportStmt,
+        IndexExpr, IterExpr, LambdaExpr, ListExpr, LogicalExpr, MapExpr, PrintStmt, ReturnStmt,
+        SetExpr, SetIndexExpr, Stmt, StringifyExpr, SuperExpr, ThisExpr, ThrowStmt, TryStmt,
+        VarExpr, VarStmt, WhileStmt, YieldStmt,
     },
 };
 
@@ -39,7 +43,8 @@ impl Parser {
     fn declaration(&mut self) -> Result<Stmt, LoxError> {
         let stmt = match self.peek().type_ {
             Class => self.class_dec(),
-            Fun => {
+            // `fun (` starts a lambda, that's an expression statement
+            Fun if !self.check_next(&LeftParen) => {
                 self.advance(); // Consume FUN token
                 self.fun_dec("function")
             }
@@ -50,9 +55,8 @@ impl Parser {
             _ => self.statement(),
         };
 
-        if let Err(lox_err) = stmt {
+        if stmt.is_err() {
             self.synchronize();
-            lox_err.report_and_exit(1);
         }
 
         stmt
@@ -72,17 +76,38 @@ impl Parser {
         self.consume(LeftBrace, "Expected '{' after class name.")?;
 
         let mut methods: Vec<FunStmt> = Vec::new();
+        let mut class_methods: Vec<FunStmt> = Vec::new();
+        let mut getters: Vec<FunStmt> = Vec::new();
+        let mut fields: Vec<VarStmt> = Vec::new();
         while !self.check(&RightBrace) && !self.is_at_end() {
-            if let Stmt::Function(fun) = self.fun_dec("method")? {
+            if self.match_token(&[Class]) {
+                if self.match_token(&[Var]) {
+                    if let Stmt::Var(field) = self.var_dec()? {
+                        fields.push(field);
+                    }
+                } else if let Stmt::Function(fun) = self.fun_dec("method")? {
+                    class_methods.push(fun);
+                }
+            } else if self.check_next(&LeftBrace) {
+                getters.push(self.getter()?);
+            } else if let Stmt::Function(fun) = self.fun_dec("method")? {
                 methods.push(fun);
             }
         }
         self.consume(RightBrace, "Expected '}' after class body.")?;
 
-        let class = ClassStmt::new(name, methods, superclass);
+        let class = ClassStmt::new(name, methods, class_methods, getters, fields, superclass);
         Ok(class.into())
     }
 
+    /// `area { return this.w * this.h; }`, a method without parameter list
+    fn getter(&mut self) -> Result<FunStmt, LoxError> {
+        let name = self.consume(Identifier, "Expect getter name")?;
+        let body = self.block_stmt()?;
+
+        Ok(FunStmt::new(name, Vec::new(), body, false))
+    }
+
     fn fun_dec(&mut self, kind: &str) -> Result<Stmt, LoxError> {
         let name = self.consume(Identifier, format!("Expect {kind} name").as_str())?;
 
@@ -90,15 +115,22 @@ impl Parser {
             LeftParen,
             format!("Expected '(' after {kind} name.").as_str(),
         )?;
+        let params = self.parameters(kind, name.line)?;
+
+        let body = self.block_stmt()?;
+
+        Ok(FunStmt::new(name, params, body, false).into())
+    }
+
+    /// Parameter names after the `(`, up to the closing `)`
+    fn parameters(&mut self, kind: &str, line: usize) -> Result<Vec<Token>, LoxError> {
         let mut params = Vec::new();
 
         // handles the zero parameters case
         if !self.check(&RightParen) {
             loop {
                 if params.len() >= 255 {
-                    ParseError::TooManyArguments(kind.to_string())
-                        .at(name.line)
-                        .report();
+                    return Err(ParseError::TooManyArguments(kind.to_string()).at(line));
                 }
 
                 params.push(self.consume(Identifier, "Expect parameter name.")?);
@@ -110,9 +142,64 @@ impl Parser {
         }
         self.consume(RightParen, "Expect ')' after parameters.")?;
 
+        Ok(params)
+    }
+
+    /// `fun (a, b) { ... }`
+    fn lambda(&mut self) -> Result<Expr, LoxError> {
+        let keyword = self.advance().clone();
+
+        self.consume(LeftParen, "Expect '(' after 'fun'.")?;
+        let params = self.parameters("function", keyword.line)?;
         let body = self.block_stmt()?;
 
-        Ok(FunStmt::new(name, params, body, false).into())
+        Ok(LambdaExpr::new(keyword, params, body).into())
+    }
+
+    /**
+     * `(a, b) => a + b` is a lambda returning `a + b`. After the arrow a `{` starts a block body, like
+     * `(a) => { print a; }`, so a lambda that returns a map literal needs parentheses around it.
+     */
+    fn arrow_lambda(&mut self) -> Result<Expr, LoxError> {
+        let paren = self.advance().clone();
+
+        let params = self.parameters("function", paren.line)?;
+        let arrow = self.consume(Arrow, "Expect '=>' after parameters.")?;
+
+        let body = if self.check(&LeftBrace) {
+            self.block_stmt()?
+        } else {
+            let value = self.expression()?;
+            Stmt::Block(vec![ReturnStmt::new(arrow, value).into()])
+        };
+
+        Ok(LambdaExpr::new(paren, params, body).into())
+    }
+
+    /// Looks past a `(` for a parameter list followed by `=>`, only then the parentheses start a lambda
+    fn is_arrow_lambda(&self) -> bool {
+        let mut i = self.current + 1;
+
+        if self.token_at(i) != Some(&RightParen) {
+            loop {
+                if self.token_at(i) != Some(&Identifier) {
+                    return false;
+                }
+                i += 1;
+
+                match self.token_at(i) {
+                    Some(Comma) => i += 1,
+                    Some(RightParen) => break,
+                    _ => return false,
+                }
+            }
+        }
+
+        self.token_at(i + 1) == Some(&Arrow)
+    }
+
+    fn token_at(&self, i: usize) -> Option<&TokenType> {
+        self.tokens.get(i).map(|token| &token.type_)
     }
 
     fn var_dec(&mut self) -> Result<Stmt, LoxError> {
@@ -139,10 +226,66 @@ impl Parser {
             While => self.while_stmt(),
             For => self.for_stmt(),
             Return => self.return_stmt(),
+            Import => self.import_stmt(),
+            Throw => self.throw_stmt(),
+            Yield => self.yield_stmt(),
+            Try => self.try_stmt(),
             _ => self.expr_stmt(),
         }
     }
 
+    fn import_stmt(&mut self) -> Result<Stmt, LoxError> {
+        let keyword = self.advance().clone();
+
+        let mut names = Vec::new();
+        if self.match_token(&[LeftBrace]) {
+            loop {
+                names.push(self.consume(Identifier, "Expect name to import.")?);
+
+                if !self.match_token(&[Comma]) {
+                    break;
+                }
+            }
+            self.consume(RightBrace, "Expect '}' after imported names.")?;
+
+            // 'from' is only a keyword here, so it can still be used as a name everywhere else
+            let from = self.consume(Identifier, "Expect 'from' after imported names.")?;
+            if from.lexeme != "from" {
+                return Err(ParseError::ExpectationFailed(
+                    "Expect 'from' after imported names.".to_string(),
+                )
+                .at(from.line));
+            }
+        }
+
+        let TokenType::String(path) = self.peek().type_.clone() else {
+            return Err(ParseError::ExpectationFailed(
+                "Expect module path after 'import'.".to_string(),
+            )
+            .at(self.peek().line));
+        };
+        self.advance();
+        self.consume(Semicolon, "Expect ';' after import.")?;
+
+        if !names.is_empty() {
+            return Ok(ImportStmt::new(keyword, path, names, None).into());
+        }
+
+        let stem = Path::new(&path)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        let is_identifier = stem.starts_with(|c: char| c.is_alphabetic() || c == '_')
+            && stem.chars().all(|c| c.is_alphanumeric() || c == '_');
+        if !is_identifier {
+            return Err(ParseError::InvalidModuleName(stem).at(keyword.line));
+        }
+
+        let namespace = Token::new(Identifier, stem, keyword.line);
+        Ok(ImportStmt::new(keyword, path, names, Some(namespace)).into())
+    }
+
     fn return_stmt(&mut self) -> Result<Stmt, LoxError> {
         let keyword = self.advance().clone();
 
@@ -155,6 +298,55 @@ impl Parser {
         Ok(ReturnStmt::new(keyword, val).into())
     }
 
+    fn yield_stmt(&mut self) -> Result<Stmt, LoxError> {
+        let keyword = self.advance().clone();
+
+        let mut value: Expr = LiteralExpr::Nil.into();
+        if !self.check(&Semicolon) {
+            value = self.expression()?;
+        }
+        self.consume(Semicolon, "Expect ';' after yielded value.")?;
+
+        Ok(YieldStmt::new(keyword, value).into())
+    }
+
+    fn throw_stmt(&mut self) -> Result<Stmt, LoxError> {
+        let keyword = self.advance().clone();
+
+        let value = self.expression()?;
+        self.consume(Semicolon, "Expect ';' after thrown value.")?;
+
+        Ok(ThrowStmt::new(keyword, value).into())
+    }
+
+    fn try_stmt(&mut self) -> Result<Stmt, LoxError> {
+        let keyword = self.advance().clone();
+        let body = self.block_stmt()?;
+
+        let mut catch = None;
+        if self.match_token(&[Catch]) {
+            self.consume(LeftParen, "Expect '(' after 'catch'.")?;
+            let name = self.consume(Identifier, "Expect error variable name.")?;
+            self.consume(RightParen, "Expect ')' after error variable.")?;
+
+            catch = Some(CatchClause::new(name, self.block_stmt()?));
+        }
+
+        let mut finally = None;
+        if self.match_token(&[Finally]) {
+            finally = Some(self.block_stmt()?);
+        }
+
+        if catch.is_none() && finally.is_none() {
+            return Err(ParseError::ExpectationFailed(
+                "Expect 'catch' or 'finally' after try block.".to_string(),
+            )
+            .at(keyword.line));
+        }
+
+        Ok(TryStmt::new(body, catch, finally).into())
+    }
+
     fn while_stmt(&mut self) -> Result<Stmt, LoxError> {
         self.advance(); // Consume 'while'
         self.consume(LeftParen, "Expect '(' after 'while'.")?;
@@ -175,6 +367,14 @@ impl Parser {
         if self.match_token(&[Semicolon]) {
             initializer = LiteralExpr::Nil.into();
         } else if self.match_token(&[Var]) {
+            // 'in' is only a keyword here, like 'from' in imports
+            let in_next = self
+                .tokens
+                .get(self.current + 1)
+                .is_some_and(|token| token.type_ == Identifier && token.lexeme == "in");
+            if self.check(&Identifier) && in_next {
+                return self.for_in_stmt();
+            }
             initializer = self.var_dec()?;
         } else {
             initializer = self.expression()?.into();
@@ -209,6 +409,38 @@ impl Parser {
         Ok(stmt)
     }
 
+    /**
+     * `for (var x in seq) body` becomes
+     * `{ var <it> = iter(seq); while (<it>.hasNext()) { var x = <it>.next(); body } }`.
+     * The iterator's name is not a valid identifier, so the body can't shadow or reassign it.
+     */
+    fn for_in_stmt(&mut self) -> Result<Stmt, LoxError> {
+        let name = self.consume(Identifier, "Expected a variable name")?;
+        let keyword = self.consume(Identifier, "Expect 'in' after the loop variable.")?;
+        let sequence = self.expression()?;
+        self.consume(RightParen, "Expect ')' after for clauses.")?;
+
+        let body = self.statement()?;
+
+        let line = keyword.line;
+        let iterator = Token::new(Identifier, " iterator".to_string(), line);
+        let method = |method: &str| -> Expr {
+            let object = VarExpr::new(iterator.clone()).into();
+            let name = Token::new(Identifier, method.to_string(), line);
+            let paren = Token::new(RightParen, ")".to_string(), line);
+
+            CallExpr::new(GetExpr::new(object, name).into(), paren, Vec::new()).into()
+        };
+
+        let next = VarStmt::new(name, method("next")).into();
+        let loop_ = WhileStmt::new(method("hasNext"), Stmt::Block(vec![next, body]));
+
+        Ok(Stmt::Block(vec![
+            VarStmt::new(iterator.clone(), IterExpr::new(keyword, sequence).into()).into(),
+            loop_.into(),
+        ]))
+    }
+
     fn if_stmt(&mut self) -> Result<Stmt, LoxError> {
         self.advance(); // Consume If token
 
@@ -240,12 +472,12 @@ impl Parser {
     }
 
     fn print_stmt(&mut self) -> Result<Stmt, LoxError> {
-        self.advance(); // Consume 'Print' token
+        let keyword = self.advance().clone();
 
         let val = self.expression()?;
         self.consume(Semicolon, "Expected ';' after value.")?;
 
-        Ok(Stmt::Print(val))
+        Ok(Stmt::Print(PrintStmt::new(keyword, val)))
     }
 
     fn expr_stmt(&mut self) -> Result<Stmt, LoxError> {
@@ -272,6 +504,14 @@ impl Parser {
             Ok(AssignmentExpr::new(var_expr.name, val).into())
         } else if let Expr::Get(get_expr) = expr {
             Ok(SetExpr::new(*get_expr.object, get_expr.name, val).into())
+        } else if let Expr::Index(index_expr) = expr {
+            Ok(SetIndexExpr::new(
+                *index_expr.object,
+                index_expr.bracket,
+                *index_expr.index,
+                val,
+            )
+            .into())
         } else {
             Err(RuntimeError::InvalidAssignment).at(self.previous().line)
         }
@@ -317,9 +557,62 @@ impl Parser {
     }
 
     fn comparison(&mut self) -> Result<Expr, LoxError> {
-        let mut expression = self.term()?;
+        let mut expression = self.bit_or()?;
 
         while self.match_token(&[Greater, GreaterEqual, Less, LessEqual]) {
+            let operator = self.previous().clone();
+            let right = self.bit_or()?;
+
+            expression = BinaryExpr::new(expression, operator, right).into()
+        }
+
+        Ok(expression)
+    }
+
+    // Bitwise operators bind tighter than comparisons, so `a & 1 == 0` is `(a & 1) == 0`
+    fn bit_or(&mut self) -> Result<Expr, LoxError> {
+        let mut expression = self.bit_xor()?;
+
+        while self.match_token(&[Pipe]) {
+            let operator = self.previous().clone();
+            let right = self.bit_xor()?;
+
+            expression = BinaryExpr::new(expression, operator, right).into()
+        }
+
+        Ok(expression)
+    }
+
+    fn bit_xor(&mut self) -> Result<Expr, LoxError> {
+        let mut expression = self.bit_and()?;
+
+        while self.match_token(&[Caret]) {
+            let operator = self.previous().clone();
+            let right = self.bit_and()?;
+
+            expression = BinaryExpr::new(expression, operator, right).into()
+        }
+
+        Ok(expression)
+    }
+
+    fn bit_and(&mut self) -> Result<Expr, LoxError> {
+        let mut expression = self.shift()?;
+
+        while self.match_token(&[Ampersand]) {
+            let operator = self.previous().clone();
+            let right = self.shift()?;
+
+            expression = BinaryExpr::new(expression, operator, right).into()
+        }
+
+        Ok(expression)
+    }
+
+    fn shift(&mut self) -> Result<Expr, LoxError> {
+        let mut expression = self.term()?;
+
+        while self.match_token(&[LessLess, GreaterGreater]) {
             let operator = self.previous().clone();
             let right = self.term()?;
 
@@ -345,7 +638,7 @@ impl Parser {
     fn factor(&mut self) -> Result<Expr, LoxError> {
         let mut expression = self.unary()?;
 
-        while self.match_token(&[Star, Slash]) {
+        while self.match_token(&[Star, Slash, TildeSlash]) {
             let operator = self.previous().clone();
             let right = self.unary()?;
 
@@ -356,16 +649,30 @@ impl Parser {
     }
 
     fn unary(&mut self) -> Result<Expr, LoxError> {
-        if self.match_token(&[Bang, Minus]) {
+        if self.match_token(&[Bang, Minus, Tilde]) {
             let operator = self.previous().clone();
             let right = self.unary()?;
 
             Ok(UnaryExpr::new(operator, right).into())
         } else {
-            self.call()
+            self.power()
         }
     }
 
+    /// `**` is right associative and binds tighter than a unary on its left: `-2 ** 2` is `-(2 ** 2)`
+    fn power(&mut self) -> Result<Expr, LoxError> {
+        let expression = self.call()?;
+
+        if self.match_token(&[StarStar]) {
+            let operator = self.previous().clone();
+            let right = self.unary()?;
+
+            return Ok(BinaryExpr::new(expression, operator, right).into());
+        }
+
+        Ok(expression)
+    }
+
     fn call(&mut self) -> Result<Expr, LoxError> {
         let mut expr = self.primary()?;
 
@@ -375,6 +682,12 @@ impl Parser {
             } else if self.match_token(&[Dot]) {
                 let name = self.consume(Identifier, "Expect property name after '.'.")?;
                 expr = GetExpr::new(expr, name).into();
+            } else if self.match_token(&[LeftBracket]) {
+                let bracket = self.previous().clone();
+                let index = self.expression()?;
+                self.consume(RightBracket, "Expect ']' after index.")?;
+
+                expr = IndexExpr::new(expr, bracket, index).into();
             } else {
                 break;
             }
@@ -389,9 +702,9 @@ impl Parser {
         if !self.check(&RightParen) {
             loop {
                 if args.len() >= 255 {
-                    ParseError::TooManyArguments(callee.clone().print())
-                        .at(self.peek().line)
-                        .report();
+                    return Err(
+                        ParseError::TooManyArguments(callee.clone().print()).at(self.peek().line)
+                    );
                 }
 
                 args.push(self.expression()?);
@@ -433,6 +746,9 @@ impl Parser {
                 self.advance();
                 LiteralExpr::String(str).into()
             }
+            Interpolation(_) => self.interpolation()?,
+            LeftParen if self.is_arrow_lambda() => self.arrow_lambda()?,
+            Fun => self.lambda()?,
             LeftParen => {
                 self.advance();
                 let expr = self.expression()?;
@@ -449,6 +765,41 @@ impl Parser {
 
                 SuperExpr::new(keyword, method).into()
             }
+            LeftBracket => {
+                let bracket = self.advance().clone();
+
+                let mut elements = Vec::new();
+                while !self.check(&RightBracket) && !self.is_at_end() {
+                    elements.push(self.expression()?);
+
+                    if !self.match_token(&[Comma]) {
+                        break;
+                    }
+                }
+                self.consume(RightBracket, "Expect ']' after list elements.")?;
+
+                ListExpr::new(bracket, elements).into()
+            }
+            // Blocks are statements, so a brace where an expression is expected can only be a map
+            LeftBrace => {
+                let brace = self.advance().clone();
+
+                let mut entries = Vec::new();
+                while !self.check(&RightBrace) && !self.is_at_end() {
+                    let key = self.expression()?;
+                    self.consume(Colon, "Expect ':' after map key.")?;
+                    let value = self.expression()?;
+
+                    entries.push((key, value));
+
+                    if !self.match_token(&[Comma]) {
+                        break;
+                    }
+                }
+                self.consume(RightBrace, "Expect '}' after map entries.")?;
+
+                MapExpr::new(brace, entries).into()
+            }
             This => ThisExpr::new(self.advance().clone()).into(),
             Identifier => VarExpr::new(self.advance().clone()).into(),
             _ => return Err(ParseError::UnexpectedEOF).at(self.peek().line),
@@ -456,6 +807,45 @@ impl Parser {
         Ok(expression)
     }
 
+    /**
+     * Desugars the pieces of an interpolated string (see `Scanner::string`) into concatenations,
+     * `"a ${b} c"` becomes `"a " + str(b) + " c"`. The conversion is the one `print` uses, so every
+     * value (numbers, instances, callables...) can be interpolated.
+     */
+    fn interpolation(&mut self) -> Result<Expr, LoxError> {
+        let start = self.advance().clone();
+        let Interpolation(head) = &start.type_ else {
+            return Err(ParseError::UnexpectedEOF).at(start.line);
+        };
+
+        let plus = Token::new(Plus, "+".to_string(), start.line);
+        let concat =
+            |left: Expr, right: Expr| -> Expr { BinaryExpr::new(left, plus.clone(), right).into() };
+
+        let mut expr: Expr = LiteralExpr::String(head.clone()).into();
+        loop {
+            let value = self.expression()?;
+            expr = concat(expr, StringifyExpr::new(start.clone(), value).into());
+
+            let part = self.peek().clone();
+            let (String(text) | Interpolation(text)) = &part.type_ else {
+                return Err(ParseError::ExpectationFailed(
+                    "Expect '}' after interpolated expression.".to_string(),
+                ))
+                .at(part.line);
+            };
+            self.advance();
+
+            if !text.is_empty() {
+                expr = concat(expr, LiteralExpr::String(text.clone()).into());
+            }
+
+            if let String(_) = part.type_ {
+                return Ok(expr);
+            }
+        }
+    }
+
     fn match_token(&mut self, types: &[TokenType]) -> bool {
         for token_type in types {
             if self.check(token_type) {
@@ -475,6 +865,10 @@ impl Parser {
         }
     }
 
+    fn check_next(&self, token_type: &TokenType) -> bool {
+        self.token_at(self.current + 1) == Some(token_type)
+    }
+
     fn peek(&self) -> &Token {
         match self.tokens.get(self.current) {
             None => self.previous(),
@@ -522,7 +916,8 @@ impl Parser {
             }
 
             match self.peek().type_ {
-                Class | Fun | Var | For | If | While | Print | Return => {
+                Class | Fun | Var | For | If | While | Print | Return | Import | Throw | Try
+                | Yield => {
                     return;
                 }
                 _ => {
@@ -0,0 +1,94 @@
+use std::env;
+
+use crate::{
+    errors::{Locate, LoxError, RuntimeError},
+    lox::{
+        ast::{Arity, LiteralExpr, NativeFn, Object},
+        native::{NativeModule, integer, string},
+    },
+};
+
+/**
+ * Natives to talk with the process running the script: its arguments, environment and exit code.
+ *
+ * `exit(code)` doesn't end the process from inside the interpreter, it returns `LoxError::Exit`,
+ * which no `catch` stops. `finally` blocks still run on the way out, and the host decides what to
+ * do with the code (the CLI exits with it).
+ */
+pub struct ProcessModule;
+
+impl NativeModule for ProcessModule {
+    fn natives(&self) -> Vec<NativeFn> {
+        vec![
+            // What follows `--` in `tw run script.lox -- a b`, the script itself is not included
+            NativeFn::new("args", Arity::Exact(0), |inter, _| {
+                let args = inter
+                    .script_args
+                    .iter()
+                    .cloned()
+                    .map(LiteralExpr::String)
+                    .collect();
+
+                Ok(inter.alloc(Object::List(args)))
+            }),
+            // `nil` when the variable is not set or its value is not valid unicode
+            NativeFn::new("getenv", Arity::Exact(1), |_, args| {
+                Ok(env::var(string(&args[0])?).map_or(LiteralExpr::Nil, LiteralExpr::String))
+            }),
+            NativeFn::new("exit", Arity::Range(0, 1), |_, args| {
+                let code = match args.first() {
+                    None => 0,
+                    Some(code) => i32::try_from(integer(code)?)
+                        .map_err(|_| RuntimeError::IntegerExpected.at(0))?,
+                };
+
+                Err(LoxError::Exit(code))
+            }),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        errors::LoxError,
+        lox::{Capture, Lox, Value},
+    };
+
+    #[test]
+    fn test_args_and_env() {
+        let mut lox = Lox::new();
+        lox.set_args(vec!["a".to_string(), "b c".to_string()]);
+
+        let res = lox.eval("str([args(), getenv(\"PATH\") != nil, getenv(\"LOX_SURELY_UNSET\")]);");
+        assert_eq!(
+            res.unwrap(),
+            Value::String("[[\"a\", \"b c\"], true, nil]".to_string())
+        );
+    }
+
+    #[test]
+    fn test_exit_unwinds_past_catch() {
+        let out = Capture::new();
+        let mut lox = Lox::new();
+        lox.set_output(out.clone());
+
+        let res = lox.eval(
+            "
+            try {
+                exit(3);
+            } catch (e) {
+                print \"caught\";
+            } finally {
+                print \"finally\";
+            }
+            print \"after\";
+            ",
+        );
+
+        assert!(matches!(res, Err(LoxError::Exit(3))), "{res:?}");
+        assert_eq!(out.contents(), "finally\n");
+        assert!(matches!(lox.eval("exit();"), Err(LoxError::Exit(0))));
+        assert!(matches!(lox.eval("exit(1.5);"), Err(LoxError::Runtime(_))));
+    }
+}
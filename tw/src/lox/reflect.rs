@@ -0,0 +1,148 @@
+use crate::{
+    errors::{Locate, LoxError, RuntimeError},
+    lox::{
+        ast::{Arity, Callable, ClassDec, ClassInstance, LiteralExpr, NativeFn, Object},
+        interpreter::Interpreter,
+        native::{NativeModule, string},
+        token::{Token, TokenType},
+    },
+};
+
+/**
+ * Natives to inspect values at runtime: their type, the class of an instance and its members.
+ * Classes are compared by identity, `classOf(p) == Point` holds for any instance `p` of `Point`.
+ */
+pub struct ReflectModule;
+
+impl NativeModule for ReflectModule {
+    fn natives(&self) -> Vec<NativeFn> {
+        vec![
+            NativeFn::new("typeOf", Arity::Exact(1), |inter, args| {
+                Ok(LiteralExpr::String(type_of(inter, &args[0]).to_string()))
+            }),
+            NativeFn::new("classOf", Arity::Exact(1), |inter, args| {
+                let class_id = instance(inter, &args[0])?.dec.id;
+
+                Ok(class_id.map_or(LiteralExpr::Nil, LiteralExpr::Call))
+            }),
+            NativeFn::new("superclassOf", Arity::Exact(1), |inter, args| {
+                let superclass = class(inter, &args[0])?.superclass.as_ref();
+
+                Ok(superclass
+                    .and_then(|superclass| superclass.id)
+                    .map_or(LiteralExpr::Nil, LiteralExpr::Call))
+            }),
+            NativeFn::new("isInstance", Arity::Exact(2), |inter, args| {
+                let class_id = class(inter, &args[1])?.id;
+                let Ok(instance) = instance(inter, &args[0]) else {
+                    return Ok(LiteralExpr::Boolean(false));
+                };
+
+                let mut dec = Some(&instance.dec);
+                while let Some(class) = dec {
+                    if class.id == class_id {
+                        return Ok(LiteralExpr::Boolean(true));
+                    }
+                    dec = class.superclass.as_deref();
+                }
+
+                Ok(LiteralExpr::Boolean(false))
+            }),
+            NativeFn::new("hasField", Arity::Exact(2), |inter, args| {
+                let name = string(&args[1])?;
+                let instance = instance(inter, &args[0])?;
+
+                Ok(LiteralExpr::Boolean(
+                    instance.fields(&inter.env).contains_key(name),
+                ))
+            }),
+            // Same as `obj.name`, getters run and methods come back bound
+            NativeFn::new("getField", Arity::Exact(2), |inter, args| {
+                let name = Token::new(TokenType::Identifier, string(&args[1])?.to_string(), 0);
+                let instance = instance(inter, &args[0])?.clone();
+
+                instance.get(&name, inter)
+            }),
+            NativeFn::new("setField", Arity::Exact(3), |inter, mut args| {
+                let value = args.pop().unwrap_or(LiteralExpr::Nil);
+                let name = Token::new(TokenType::Identifier, string(&args[1])?.to_string(), 0);
+                let LiteralExpr::Instance(obj_id) = args[0] else {
+                    return Err(RuntimeError::InstanceExpected.at(0));
+                };
+
+                match &mut inter.heap[obj_id] {
+                    Object::Instance(instance) => instance.set(name, value.clone(), &mut inter.env),
+                    _ => return Err(RuntimeError::InstanceExpected.at(0)),
+                }
+
+                Ok(value)
+            }),
+            NativeFn::new("fields", Arity::Exact(1), |inter, args| {
+                let instance = instance(inter, &args[0])?;
+                let names = instance.fields(&inter.env).keys().cloned().collect();
+
+                Ok(name_list(inter, names))
+            }),
+            // Methods of the class and the ones it inherits, getters and class methods are not included
+            NativeFn::new("methods", Arity::Exact(1), |inter, args| {
+                let mut names = Vec::new();
+                let mut dec = Some(class(inter, &args[0])?);
+                while let Some(class) = dec {
+                    names.extend(class.methods.keys().cloned());
+                    dec = class.superclass.as_deref();
+                }
+
+                Ok(name_list(inter, names))
+            }),
+        ]
+    }
+}
+
+fn type_of(inter: &Interpreter, value: &LiteralExpr) -> &'static str {
+    match value {
+        LiteralExpr::Nil => "nil",
+        LiteralExpr::Boolean(_) => "boolean",
+        LiteralExpr::Number(_) => "number",
+        LiteralExpr::String(_) => "string",
+        LiteralExpr::Call(id) => match &inter.heap[*id] {
+            Object::Callable(Callable::Class(_)) => "class",
+            _ => "function",
+        },
+        LiteralExpr::Instance(_) => "instance",
+        LiteralExpr::Generator(_) => "generator",
+        LiteralExpr::List(_) => "list",
+        LiteralExpr::Map(_) => "map",
+    }
+}
+
+/// Sorted, since fields have no order of their own, and without the duplicates of overridden methods
+fn name_list(inter: &mut Interpreter, mut names: Vec<String>) -> LiteralExpr {
+    names.sort();
+    names.dedup();
+
+    let names = names.into_iter().map(LiteralExpr::String).collect();
+    inter.alloc(Object::List(names))
+}
+
+fn instance<'a>(
+    inter: &'a Interpreter,
+    value: &LiteralExpr,
+) -> Result<&'a ClassInstance, LoxError> {
+    match value {
+        LiteralExpr::Instance(id) => match &inter.heap[*id] {
+            Object::Instance(instance) => Ok(instance),
+            _ => Err(RuntimeError::InstanceExpected.at(0)),
+        },
+        _ => Err(RuntimeError::InstanceExpected.at(0)),
+    }
+}
+
+fn class<'a>(inter: &'a Interpreter, value: &LiteralExpr) -> Result<&'a ClassDec, LoxError> {
+    match value {
+        LiteralExpr::Call(id) => match &inter.heap[*id] {
+            Object::Callable(Callable::Class(class)) => Ok(class),
+            _ => Err(RuntimeError::ClassExpected.at(0)),
+        },
+        _ => Err(RuntimeError::ClassExpected.at(0)),
+    }
+}
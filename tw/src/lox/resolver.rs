@@ -5,8 +5,9 @@ use crate::{
     lox::{
         ast::{
             AssignmentExpr, BinaryExpr, CallExpr, ClassStmt, Expr, FunStmt, GetExpr, GroupingExpr,
-            IfStmt, LiteralExpr, LogicalExpr, ReturnStmt, SetExpr, Stmt, SuperExpr, ThisExpr,
-            UnaryExpr, VarExpr, VarStmt, WhileStmt,
+            IfStmt, ImportStmt, IndexExpr, ListExpr, LiteralExpr, LogicalExpr, MapExpr, ReturnStmt,
+            SetExpr, SetIndexExpr, Stmt, SuperExpr, ThisExpr, TryStmt, UnaryExpr, VarExpr, VarStmt,
+            WhileStmt, YieldStmt,
         },
         interpreter::Interpreter,
         token::Token,
@@ -25,6 +26,8 @@ enum FunctionType {
 enum ClassType {
     Class,
     Subclass,
+    /// Class methods, they run without an instance so `this` and `super` are not bound
+    Static,
     None,
 }
 
@@ -33,6 +36,9 @@ pub struct Resolver {
     scopes: Vec<HashMap<String, bool>>,
     function: FunctionType,
     class: ClassType,
+    /// A `yield` was found in the function being resolved
+    generator: bool,
+    in_finally: bool,
 }
 
 impl Resolver {
@@ -42,6 +48,8 @@ impl Resolver {
             scopes: Vec::new(),
             function: FunctionType::None,
             class: ClassType::None,
+            generator: false,
+            in_finally: false,
         }
     }
 
@@ -52,10 +60,14 @@ impl Resolver {
             Stmt::Function(fun) => self.rs_fun_stmt(fun),
             Stmt::If(if_) => self.rs_if_stmt(if_),
             Stmt::Block(stmts) => self.rs_block_stmt(stmts),
-            Stmt::Print(value) => self.rs_print_stmt(value),
+            Stmt::Print(print) => self.rs_print_stmt(&mut print.value),
             Stmt::Return(value) => self.rs_return_stmt(value),
             Stmt::While(while_) => self.rs_while_stmt(while_),
             Stmt::Class(class) => self.rs_class_stmt(class),
+            Stmt::Import(import) => self.rs_import_stmt(import),
+            Stmt::Throw(throw) => self.rs_expression(&mut throw.value),
+            Stmt::Try(try_) => self.rs_try_stmt(try_),
+            Stmt::Yield(yield_) => self.rs_yield_stmt(yield_),
         }
     }
 
@@ -73,6 +85,9 @@ impl Resolver {
             Expr::Assign(assign) => self.rs_assign_expr(assign),
             Expr::Var(var) => self.rs_var_expr(var),
             Expr::Grouping(group) => self.rs_group_expr(group),
+            Expr::Stringify(stringify) => self.rs_expression(&mut stringify.expression),
+            Expr::Iter(iter) => self.rs_expression(&mut iter.expression),
+            Expr::Lambda(lambda) => self.rs_function(&mut lambda.function, FunctionType::Function),
             Expr::Binary(bin) => self.rs_binary_expr(bin),
             Expr::Call(call) => self.rs_call_expr(call),
             Expr::Logical(logic) => self.rs_logic_expr(logic),
@@ -80,6 +95,10 @@ impl Resolver {
             Expr::Get(get) => self.rs_get_expr(get),
             Expr::Set(set) => self.rs_set_expr(set),
             Expr::This(this) => self.rs_this_expr(this),
+            Expr::List(list) => self.rs_list_expr(list),
+            Expr::Map(map) => self.rs_map_expr(map),
+            Expr::Index(index) => self.rs_index_expr(index),
+            Expr::SetIndex(set) => self.rs_set_index_expr(set),
             Expr::Literal(_) => Ok(()),
         }
     }
@@ -121,13 +140,39 @@ impl Resolver {
             self.rs_function(method, fn_type)?;
         }
 
+        for getter in &mut class.getters {
+            self.rs_function(getter, FunctionType::Method)?;
+        }
+
         self.end_scope();
+
+        let class_type = self.class;
+        self.class = ClassType::Static;
+        for method in &mut class.class_methods {
+            self.rs_function(method, FunctionType::Method)?;
+        }
+        self.class = class_type;
+
         if let Some(_) = class.superclass {
             self.end_scope();
         }
 
         self.class = enclosing_class;
 
+        // Static fields are evaluated once the class exists, in the scope that declares it
+        for field in &mut class.fields {
+            self.rs_expression(&mut field.val)?;
+        }
+
+        Ok(())
+    }
+
+    fn rs_import_stmt(&mut self, import: &mut ImportStmt) -> Result<(), LoxError> {
+        for name in import.names.iter().chain(&import.namespace) {
+            self.declare(name)?;
+            self.define(name)?;
+        }
+
         Ok(())
     }
 
@@ -140,7 +185,11 @@ impl Resolver {
 
     fn rs_function(&mut self, fun: &mut FunStmt, type_: FunctionType) -> Result<(), LoxError> {
         let enclosing_fn = self.function;
+        let enclosing_generator = self.generator;
+        let enclosing_finally = self.in_finally;
         self.function = type_;
+        self.generator = false;
+        self.in_finally = false;
 
         self.begin_scope();
         for param in &fun.params {
@@ -150,7 +199,11 @@ impl Resolver {
         self.resolve(&mut fun.body)?;
         self.end_scope();
 
+        fun.is_generator = self.generator;
+
         self.function = enclosing_fn;
+        self.generator = enclosing_generator;
+        self.in_finally = enclosing_finally;
 
         Ok(())
     }
@@ -186,6 +239,41 @@ impl Resolver {
         self.rs_expression(&mut return_.value)
     }
 
+    fn rs_yield_stmt(&mut self, yield_: &mut YieldStmt) -> Result<(), LoxError> {
+        let line = yield_.keyword.line;
+
+        match self.function {
+            FunctionType::None => return Err(ParseError::TopLevelYield.at(line)),
+            FunctionType::Initializer => return Err(ParseError::YieldInAnInitializer.at(line)),
+            _ if self.in_finally => return Err(ParseError::YieldInFinally.at(line)),
+            _ => {}
+        }
+
+        self.generator = true;
+        self.rs_expression(&mut yield_.value)
+    }
+
+    fn rs_try_stmt(&mut self, try_: &mut TryStmt) -> Result<(), LoxError> {
+        self.resolve(&mut try_.body)?;
+
+        if let Some(catch) = &mut try_.catch {
+            self.begin_scope();
+            self.declare(&catch.name)?;
+            self.define(&catch.name)?;
+            self.resolve(&mut catch.body)?;
+            self.end_scope();
+        }
+
+        if let Some(finally) = &mut try_.finally {
+            let enclosing_finally = self.in_finally;
+            self.in_finally = true;
+            self.resolve(finally)?;
+            self.in_finally = enclosing_finally;
+        }
+
+        Ok(())
+    }
+
     fn rs_while_stmt(&mut self, while_: &mut WhileStmt) -> Result<(), LoxError> {
         self.rs_expression(&mut while_.condition)?;
 
@@ -195,6 +283,8 @@ impl Resolver {
     fn rs_super_expr(&mut self, super_: &mut SuperExpr) -> Result<(), LoxError> {
         if self.class == ClassType::None {
             return Err(ParseError::OutsideSuper.at(super_.keyword.line));
+        } else if self.class == ClassType::Static {
+            return Err(ParseError::StaticSuper.at(super_.keyword.line));
         } else if self.class != ClassType::Subclass {
             return Err(ParseError::SuperWithNoSuperclass.at(super_.keyword.line));
         }
@@ -203,8 +293,10 @@ impl Resolver {
     }
 
     fn rs_this_expr(&mut self, this: &mut ThisExpr) -> Result<(), LoxError> {
-        if let ClassType::None = self.class {
-            return Err(ParseError::OutsideThis.at(this.keyword.line));
+        match self.class {
+            ClassType::None => return Err(ParseError::OutsideThis.at(this.keyword.line)),
+            ClassType::Static => return Err(ParseError::StaticThis.at(this.keyword.line)),
+            _ => {}
         }
 
         self.resolve_local("this", &mut this.depth)
@@ -215,6 +307,34 @@ impl Resolver {
         self.rs_expression(&mut set.value)
     }
 
+    fn rs_list_expr(&mut self, list: &mut ListExpr) -> Result<(), LoxError> {
+        for element in &mut list.elements {
+            self.rs_expression(element)?;
+        }
+
+        Ok(())
+    }
+
+    fn rs_map_expr(&mut self, map: &mut MapExpr) -> Result<(), LoxError> {
+        for (key, value) in &mut map.entries {
+            self.rs_expression(key)?;
+            self.rs_expression(value)?;
+        }
+
+        Ok(())
+    }
+
+    fn rs_index_expr(&mut self, index: &mut IndexExpr) -> Result<(), LoxError> {
+        self.rs_expression(&mut index.object)?;
+        self.rs_expression(&mut index.index)
+    }
+
+    fn rs_set_index_expr(&mut self, set: &mut SetIndexExpr) -> Result<(), LoxError> {
+        self.rs_expression(&mut set.object)?;
+        self.rs_expression(&mut set.index)?;
+        self.rs_expression(&mut set.value)
+    }
+
     fn rs_get_expr(&mut self, get: &mut GetExpr) -> Result<(), LoxError> {
         self.rs_expression(&mut get.object)
     }
@@ -375,4 +495,46 @@ mod tests {
         let msg = format!("{:?}", err);
         assert!(msg.contains("TopLevelReturn") || msg.contains("PARSE"));
     }
+
+    #[test]
+    fn test_this_in_class_members() {
+        let getter = "
+            class Square {
+                area { return this.side * this.side; }
+            }
+        ";
+        assert!(resolve_src(getter).is_ok());
+
+        let class_method = "
+            class Square {
+                class unit() { return () => this; }
+            }
+        ";
+        let err = resolve_src(class_method).unwrap_err();
+        assert!(format!("{:?}", err).contains("StaticThis"));
+
+        let class_super = "
+            class Shape {}
+            class Square < Shape {
+                class unit() { return super.unit(); }
+            }
+        ";
+        let err = resolve_src(class_super).unwrap_err();
+        assert!(format!("{:?}", err).contains("StaticSuper"));
+    }
+
+    #[test]
+    fn test_yield_placement() {
+        let generator = "fun gen() { while (true) { yield 1; } }";
+        assert!(resolve_src(generator).is_ok());
+
+        for (src, error) in [
+            ("yield 1;", "TopLevelYield"),
+            ("class A { init() { yield 1; } }", "YieldInAnInitializer"),
+            ("fun f() { try {} finally { yield 1; } }", "YieldInFinally"),
+        ] {
+            let err = resolve_src(src).unwrap_err();
+            assert!(format!("{:?}", err).contains(error));
+        }
+    }
 }
@@ -1,9 +1,10 @@
-use std::{collections::BTreeMap, fs, io};
+use std::{collections::BTreeMap, fs, io, path::Path, process};
 
 use crate::{
     cli::alerts::Alert,
     errors::{IoError, LoxError},
     lox::{
+        fs::Permissions,
         interpreter::Interpreter,
         resolver::Resolver,
         scanner::Scanner,
@@ -18,6 +19,9 @@ pub struct RunOptsCommand {
     pub debug: bool,
     pub show_ast: bool,
     pub show_tokens: bool,
+    pub include: Vec<String>,
+    pub permissions: Permissions,
+    pub args: Vec<String>,
 }
 impl Default for RunOptsCommand {
     fn default() -> Self {
@@ -25,6 +29,9 @@ impl Default for RunOptsCommand {
             debug: false,
             show_ast: false,
             show_tokens: false,
+            include: Vec::new(),
+            permissions: Permissions::default(),
+            args: Vec::new(),
         }
     }
 }
@@ -34,12 +41,15 @@ pub fn handle_run_command(path: Option<String>, opts: RunOptsCommand) {
         debug,
         show_ast,
         show_tokens,
+        include,
+        permissions,
+        args,
     } = opts;
 
     let source: String;
 
-    if let Some(path) = path {
-        let valid_path = handle_path_format(&path);
+    if let Some(path) = &path {
+        let valid_path = handle_path_format(path);
         source = read_file(&valid_path);
     } else {
         Alert::info("CLI | No file path provided, reading from prompt...".to_string()).show();
@@ -52,24 +62,50 @@ pub fn handle_run_command(path: Option<String>, opts: RunOptsCommand) {
         }
     }
 
-    let tokens = Scanner::scan_from(source.to_string());
+    let mut interpreter = Interpreter::new();
+    interpreter.set_permissions(permissions);
+    interpreter.set_args(args);
+    for dir in include {
+        interpreter.add_search_path(dir);
+    }
+
+    if let Some(path) = &path {
+        interpreter.set_script_path(Path::new(path));
+    }
+
+    // Diagnostics go to the error sink of the interpreter (stderr), the output of the script alone
+    // goes to stdout
+    let in_file = |err: LoxError| match &path {
+        Some(path) => err.in_file(path),
+        None => err,
+    };
+
+    let (tokens, scan_errors) = Scanner::scan_from(source.to_string());
+    for err in scan_errors {
+        interpreter.report(&in_file(err));
+    }
 
     if debug && !show_ast && !show_tokens {
         Alert::info("CLI | Debug mode is enabled.".to_string()).show();
         debug_show_tokens(tokens.clone());
-        debug_show_ast(tokens.clone());
+        debug_show_ast(tokens.clone(), &mut interpreter);
     }
 
     if show_ast {
-        debug_show_ast(tokens.clone());
+        debug_show_ast(tokens.clone(), &mut interpreter);
     }
 
     if show_tokens {
         debug_show_tokens(tokens.clone());
     }
 
-    if let Err(lang_err) = run(tokens.clone()) {
-        lang_err.report_and_exit(1)
+    if let Err(lang_err) = run(tokens, &mut interpreter) {
+        if let LoxError::Exit(code) = lang_err {
+            process::exit(code);
+        }
+
+        interpreter.report(&in_file(lang_err));
+        process::exit(1);
     }
 }
 
@@ -115,14 +151,17 @@ fn read_prompt() -> String {
     source
 }
 
-fn run(tokens: Vec<Token>) -> Result<(), LoxError> {
+fn run(tokens: Vec<Token>, interpreter: &mut Interpreter) -> Result<(), LoxError> {
     let mut parser = Parser::new(tokens);
 
     let mut statements = parser.parse()?;
-    let mut resolver = Resolver::new(Interpreter::new());
-    resolver.resolve_stmts(&mut statements)?;
 
-    let mut interpreter = resolver.interpreter;
+    // The resolver owns the interpreter while it runs, it is given back to report the errors
+    let mut resolver = Resolver::new(std::mem::take(interpreter));
+    let resolved = resolver.resolve_stmts(&mut statements);
+    *interpreter = resolver.interpreter;
+    resolved?;
+
     interpreter.interpret(statements)?;
 
     Ok(())
@@ -134,7 +173,7 @@ fn debug_show_tokens(tokens: Vec<Token>) {
     }
 }
 
-fn debug_show_ast(tokens: Vec<Token>) {
+fn debug_show_ast(tokens: Vec<Token>, interpreter: &mut Interpreter) {
     let mut tokens_by_line: BTreeMap<usize, Vec<Token>> = BTreeMap::new();
 
     for token in tokens {
@@ -170,7 +209,7 @@ fn debug_show_ast(tokens: Vec<Token>) {
             }
             Err(lox_error) => {
                 // Report and continue to next line instead of exiting
-                lox_error.report();
+                interpreter.report(&lox_error);
                 continue;
             }
         }
@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 
-use crate::errors::{Locate, ScanError};
+use crate::errors::{Locate, LoxError, ScanError};
 
 use super::token::{Token, TokenType};
 
@@ -8,31 +8,41 @@ use super::token::{Token, TokenType};
 pub(super) struct Scanner {
     source: String,
     tokens: Vec<Token>,
+    errors: Vec<LoxError>,
 
     start: usize,
     current: usize,
     line: usize,
+
+    /// Open `${` in strings, each one with the count of `{` opened inside it
+    interpolations: Vec<usize>,
 }
 
 fn keywords() -> HashMap<&'static str, TokenType> {
     let mut keywords = HashMap::new();
 
     keywords.insert("and", TokenType::And);
+    keywords.insert("catch", TokenType::Catch);
     keywords.insert("class", TokenType::Class);
     keywords.insert("else", TokenType::Else);
     keywords.insert("false", TokenType::False);
+    keywords.insert("finally", TokenType::Finally);
     keywords.insert("for", TokenType::For);
     keywords.insert("fun", TokenType::Fun);
     keywords.insert("if", TokenType::If);
+    keywords.insert("import", TokenType::Import);
     keywords.insert("nil", TokenType::Nil);
     keywords.insert("or", TokenType::Or);
     keywords.insert("print", TokenType::Print);
     keywords.insert("return", TokenType::Return);
     keywords.insert("super", TokenType::Super);
     keywords.insert("this", TokenType::This);
+    keywords.insert("throw", TokenType::Throw);
     keywords.insert("true", TokenType::True);
+    keywords.insert("try", TokenType::Try);
     keywords.insert("var", TokenType::Var);
     keywords.insert("while", TokenType::While);
+    keywords.insert("yield", TokenType::Yield);
 
     keywords
 }
@@ -42,15 +52,47 @@ impl Scanner {
         Scanner {
             source,
             tokens: Vec::new(),
+            errors: Vec::new(),
             start: 0,
             current: 0,
             line: 1,
+            interpolations: Vec::new(),
         }
     }
 
-    pub fn scan_from(source: String) -> Vec<Token> {
+    /// Tokens of `source` and every error found scanning it, the tokens around an error are kept
+    pub fn scan_from(source: String) -> (Vec<Token>, Vec<LoxError>) {
+        let mut scanner = Scanner::new(source);
+        scanner.scan_tokens();
+
+        (scanner.tokens, scanner.errors)
+    }
+
+    /// Same as `scan_from`, but it stops at the first scan error
+    pub fn try_scan_from(source: String) -> Result<Vec<Token>, LoxError> {
         let mut scanner = Scanner::new(source);
-        scanner.scan_tokens().clone()
+        scanner.scan_tokens();
+
+        match scanner.errors.into_iter().next() {
+            Some(error) => Err(error),
+            None => Ok(scanner.tokens),
+        }
+    }
+
+    /// Value of `text` when all of it is one number literal, `num()` converts strings with it
+    pub fn number_literal(text: &str) -> Option<f64> {
+        if !text.starts_with(|c: char| c.is_ascii_digit()) {
+            return None;
+        }
+
+        let mut scanner = Scanner::new(text.to_string());
+        scanner.advance();
+        scanner.number();
+
+        match scanner.tokens.pop()?.type_ {
+            TokenType::Number(n) if scanner.is_at_end() => Some(n),
+            _ => None,
+        }
     }
 }
 
@@ -62,6 +104,11 @@ impl Scanner {
             self.scan_token();
         }
 
+        // A string left open inside the interpolation already reported itself
+        if !self.interpolations.is_empty() && self.errors.is_empty() {
+            self.error(ScanError::UnterminatedString);
+        }
+
         self.tokens
             .push(Token::new(TokenType::EOF, String::new(), self.line));
         &self.tokens
@@ -73,14 +120,54 @@ impl Scanner {
         match c {
             '(' => self.add_token(TokenType::LeftParen),
             ')' => self.add_token(TokenType::RightParen),
-            '{' => self.add_token(TokenType::LeftBrace),
-            '}' => self.add_token(TokenType::RightBrace),
+            '{' => {
+                if let Some(depth) = self.interpolations.last_mut() {
+                    *depth += 1;
+                }
+
+                self.add_token(TokenType::LeftBrace)
+            }
+            // The `}` that closes an interpolation resumes the string it started in
+            '}' if self.interpolations.last() == Some(&0) => {
+                self.interpolations.pop();
+                self.string();
+            }
+            '}' => {
+                if let Some(depth) = self.interpolations.last_mut() {
+                    *depth -= 1;
+                }
+
+                self.add_token(TokenType::RightBrace)
+            }
+            '[' => self.add_token(TokenType::LeftBracket),
+            ']' => self.add_token(TokenType::RightBracket),
             ',' => self.add_token(TokenType::Comma),
+            ':' => self.add_token(TokenType::Colon),
             '.' => self.add_token(TokenType::Dot),
             '-' => self.add_token(TokenType::Minus),
             '+' => self.add_token(TokenType::Plus),
             ';' => self.add_token(TokenType::Semicolon),
-            '*' => self.add_token(TokenType::Star),
+            '*' => {
+                let token_type = if self.match_char('*') {
+                    TokenType::StarStar
+                } else {
+                    TokenType::Star
+                };
+
+                self.add_token(token_type)
+            }
+            '&' => self.add_token(TokenType::Ampersand),
+            '|' => self.add_token(TokenType::Pipe),
+            '^' => self.add_token(TokenType::Caret),
+            '~' => {
+                let token_type = if self.match_char('/') {
+                    TokenType::TildeSlash
+                } else {
+                    TokenType::Tilde
+                };
+
+                self.add_token(token_type)
+            }
             '!' => {
                 let token_type = if self.match_char('=') {
                     TokenType::BangEqual
@@ -93,6 +180,8 @@ impl Scanner {
             '=' => {
                 let token_type = if self.match_char('=') {
                     TokenType::EqualEqual
+                } else if self.match_char('>') {
+                    TokenType::Arrow
                 } else {
                     TokenType::Equal
                 };
@@ -102,6 +191,8 @@ impl Scanner {
             '<' => {
                 let token_type = if self.match_char('=') {
                     TokenType::LessEqual
+                } else if self.match_char('<') {
+                    TokenType::LessLess
                 } else {
                     TokenType::Less
                 };
@@ -111,6 +202,8 @@ impl Scanner {
             '>' => {
                 let token_type = if self.match_char('=') {
                     TokenType::GreaterEqual
+                } else if self.match_char('>') {
+                    TokenType::GreaterGreater
                 } else {
                     TokenType::Greater
                 };
@@ -131,7 +224,7 @@ impl Scanner {
 
                         // Handle EOF inside block comment
                         if self.is_at_end() {
-                            ScanError::UnterminatedString.at(self.line).report(); // Using UnterminatedString error for block comment? 
+                            self.error(ScanError::UnterminatedString); // Using UnterminatedString error for block comment? 
                             return;
                         }
 
@@ -153,10 +246,15 @@ impl Scanner {
             ' ' | '\r' | '\t' => {} // Ignore whitespace
             '\n' => self.line += 1,
             '"' => self.string(),
+            'r' if self.peek() == '"' => {
+                // Consume the opening "
+                self.advance();
+                self.raw_string();
+            }
             ch if ch.is_ascii_digit() => self.number(),
             ch if ch.is_ascii_alphabetic() || ch == '_' => self.identifier(),
             ch => {
-                ScanError::UnexpectedChar(ch).at(self.line).report();
+                self.error(ScanError::UnexpectedChar(ch));
             }
         };
     }
@@ -168,12 +266,17 @@ impl Scanner {
             source,
             tokens,
             line,
+            ..
         } = self;
 
         let text = &source[*start..*current];
         tokens.push(Token::new(token_type, text.to_string(), *line));
     }
 
+    fn error(&mut self, error: ScanError) {
+        self.errors.push(error.at(self.line));
+    }
+
     fn is_at_end(&self) -> bool {
         self.current >= self.source.len()
     }
@@ -213,48 +316,188 @@ impl Scanner {
         }
     }
 
+    /**
+     * String tokens take the line they start on, a multi-line string still moves `line` forward.
+     *
+     * `"a ${b} c"` is scanned as `Interpolation("a ")`, the tokens of `b`, then `String(" c")`. The parser
+     * puts the pieces back together (see `Parser::interpolation`).
+     */
     fn string(&mut self) {
+        let line = self.line;
+        let mut literal = String::new();
+
         while self.peek() != '"' && !self.is_at_end() {
-            if self.peek() == '\n' {
-                self.line += 1;
+            let c = self.advance();
+
+            match c {
+                '$' if self.match_char('{') => {
+                    self.interpolations.push(0);
+                    self.add_token_at(TokenType::Interpolation(literal), line);
+                    return;
+                }
+                '\\' => {
+                    if let Some(escaped) = self.escape() {
+                        literal.push(escaped);
+                    }
+                }
+                '\n' => {
+                    self.line += 1;
+                    literal.push(c);
+                }
+                _ => literal.push(c),
             }
+        }
 
-            self.advance();
+        if self.is_at_end() {
+            self.errors.push(ScanError::UnterminatedString.at(line));
+            return;
+        }
+
+        // The closing "
+        self.advance();
+
+        self.add_token_at(TokenType::String(literal), line);
+    }
+
+    /// Raw strings (`r"..."`) keep backslashes as they are and can't contain `"`
+    fn raw_string(&mut self) {
+        let line = self.line;
+
+        while self.peek() != '"' && !self.is_at_end() {
+            if self.advance() == '\n' {
+                self.line += 1;
+            }
         }
 
         if self.is_at_end() {
-            ScanError::UnterminatedString.at(self.line).report();
+            self.errors.push(ScanError::UnterminatedString.at(line));
             return;
         }
 
         // The closing "
         self.advance();
 
-        // Trim the surrounding quotes
-        let literal = &self.source[(self.start + 1)..(self.current - 1)];
-        self.add_token(TokenType::String(literal.to_string()));
+        // Trim the prefix and the surrounding quotes
+        let literal = self.source[(self.start + 2)..(self.current - 1)].to_string();
+        self.add_token_at(TokenType::String(literal), line);
     }
 
-    fn number(&mut self) {
-        while self.peek().is_ascii_digit() {
-            self.advance();
+    /// The character after a `\`, `None` when the escape is invalid (the error is already recorded)
+    fn escape(&mut self) -> Option<char> {
+        if self.is_at_end() {
+            return None;
+        }
+
+        let escaped = match self.advance() {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            '0' => '\0',
+            '\\' => '\\',
+            '"' => '"',
+            '\'' => '\'',
+            '$' => '$',
+            'u' => return self.unicode_escape(),
+            c => {
+                if c == '\n' {
+                    self.line += 1;
+                }
+
+                self.error(ScanError::InvalidEscape(c));
+                return None;
+            }
+        };
+
+        Some(escaped)
+    }
+
+    /// `\u{...}` with 1 to 6 hex digits naming a unicode scalar value
+    fn unicode_escape(&mut self) -> Option<char> {
+        let mut digits = String::new();
+
+        if self.match_char('{') {
+            while self.peek().is_ascii_hexdigit() {
+                digits.push(self.advance());
+            }
         }
 
-        // Look for a fractional part
-        if self.peek() == '.' && self.peek_next().is_ascii_digit() {
-            // Consume the "."
+        let code = Some(digits)
+            .filter(|digits| (1..=6).contains(&digits.len()))
+            .filter(|_| self.match_char('}'))
+            .and_then(|digits| u32::from_str_radix(&digits, 16).ok())
+            .and_then(char::from_u32);
+
+        if code.is_none() {
+            self.error(ScanError::InvalidUnicodeEscape);
+        }
+
+        code
+    }
+
+    fn add_token_at(&mut self, token_type: TokenType, line: usize) {
+        let text = &self.source[self.start..self.current];
+        self.tokens
+            .push(Token::new(token_type, text.to_string(), line));
+    }
+
+    /// Decimals with an optional fraction and exponent, `0x` hex and `0b` binary, all with `_` separators
+    fn number(&mut self) {
+        let first = &self.source[self.start..self.current];
+        let radix = match (first, self.peek()) {
+            ("0", 'x' | 'X') => 16,
+            ("0", 'b' | 'B') => 2,
+            _ => 10,
+        };
+
+        if radix != 10 {
+            // Consume the prefix
             self.advance();
+            self.digits(radix);
+        } else {
+            self.digits(10);
 
-            while self.peek().is_ascii_digit() {
+            // Look for a fractional part
+            if self.peek() == '.' && self.peek_next().is_ascii_digit() {
+                // Consume the "."
                 self.advance();
+                self.digits(10);
+            }
+
+            // Look for an exponent, its sign is optional
+            let sign = matches!(self.peek_next(), '+' | '-');
+            let exponent_digit = match sign {
+                true => self.source[self.current..].chars().nth(2),
+                false => Some(self.peek_next()),
+            };
+            if matches!(self.peek(), 'e' | 'E')
+                && exponent_digit.is_some_and(|c| c.is_ascii_digit())
+            {
+                self.advance();
+                if sign {
+                    self.advance();
+                }
+                self.digits(10);
             }
         }
 
-        let literal: f64 = match &self.source[self.start..self.current].parse() {
-            Ok(n) => *n,
-            Err(..) => 0.0, // Fallback value, shouldn't happen with valid scan
-        };
-        self.add_token(TokenType::Number(literal));
+        // `0b102` or `12abc` are one bad literal, not a number followed by something else
+        let mut invalid = false;
+        while self.peek().is_alphanumeric() || self.peek() == '_' {
+            invalid = true;
+            self.advance();
+        }
+
+        let text = &self.source[self.start..self.current];
+        match parse_number(text, radix).filter(|_| !invalid) {
+            Some(literal) => self.add_token(TokenType::Number(literal)),
+            None => self.error(ScanError::InvalidNumber(text.to_string())),
+        }
+    }
+
+    fn digits(&mut self, radix: u32) {
+        while self.peek().is_digit(radix) || self.peek() == '_' {
+            self.advance();
+        }
     }
 
     fn peek_next(&self) -> char {
@@ -281,6 +524,30 @@ impl Scanner {
     }
 }
 
+/// Value of a scanned number literal, `None` when a `_` isn't between two digits or there are no digits
+fn parse_number(text: &str, radix: u32) -> Option<f64> {
+    let body = if radix == 10 { text } else { &text[2..] };
+
+    let chars: Vec<char> = body.chars().collect();
+    let separators_ok = chars.iter().enumerate().all(|(i, c)| {
+        *c != '_'
+            || (i > 0
+                && i + 1 < chars.len()
+                && chars[i - 1].is_digit(radix)
+                && chars[i + 1].is_digit(radix))
+    });
+    if !separators_ok {
+        return None;
+    }
+
+    let digits = body.replace('_', "");
+    if radix == 10 {
+        digits.parse().ok()
+    } else {
+        u64::from_str_radix(&digits, radix).ok().map(|n| n as f64)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -347,6 +614,145 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_string_escapes() {
+        let mut scanner = Scanner::new(r#""a\tb\n\"q\" \\ \u{e9}\u{1F600}" r"C:\new""#.to_string());
+        let tokens = scanner.scan_tokens();
+
+        assert_eq!(
+            tokens[0].type_,
+            TokenType::String("a\tb\n\"q\" \\ \u{e9}\u{1F600}".to_string())
+        );
+        assert_eq!(tokens[1].type_, TokenType::String("C:\\new".to_string()));
+    }
+
+    #[test]
+    fn test_multi_line_strings() {
+        let mut scanner = Scanner::new("\"one\ntwo\" r\"three\nfour\" x".to_string());
+        let tokens = scanner.scan_tokens();
+
+        assert_eq!(tokens[0].type_, TokenType::String("one\ntwo".to_string()));
+        assert_eq!(tokens[0].line, 1);
+        assert_eq!(
+            tokens[1].type_,
+            TokenType::String("three\nfour".to_string())
+        );
+        assert_eq!(tokens[1].line, 2);
+        assert_eq!(tokens[2].line, 3);
+    }
+
+    #[test]
+    fn test_invalid_escapes() {
+        let errors = |src: &str| {
+            let mut scanner = Scanner::new(src.to_string());
+            scanner.scan_tokens();
+
+            scanner.errors
+        };
+
+        assert!(matches!(
+            &errors(r#""\q""#)[..],
+            [LoxError::Scan(err)] if err.error == ScanError::InvalidEscape('q')
+        ));
+        for src in [
+            r#""\u{}""#,
+            r#""\u{110000}""#,
+            r#""\u41""#,
+            r#""\u{1234567}""#,
+        ] {
+            assert!(matches!(
+                &errors(src)[..],
+                [LoxError::Scan(err)] if err.error == ScanError::InvalidUnicodeEscape
+            ));
+        }
+        assert!(matches!(
+            &errors("\"\n\nabc\\")[..],
+            [LoxError::Scan(err)] if err.error == ScanError::UnterminatedString && err.line == 1
+        ));
+    }
+
+    #[test]
+    fn test_interpolated_strings() {
+        let mut scanner = Scanner::new(r#""a ${ {"b": 1}["b"] } c ${d}" "\${e}""#.to_string());
+        let tokens = scanner.scan_tokens();
+
+        let types: Vec<TokenType> = tokens.iter().map(|t| t.type_.clone()).collect();
+        assert_eq!(
+            types,
+            vec![
+                TokenType::Interpolation("a ".to_string()),
+                TokenType::LeftBrace,
+                TokenType::String("b".to_string()),
+                TokenType::Colon,
+                TokenType::Number(1.0),
+                TokenType::RightBrace,
+                TokenType::LeftBracket,
+                TokenType::String("b".to_string()),
+                TokenType::RightBracket,
+                TokenType::Interpolation(" c ".to_string()),
+                TokenType::Identifier,
+                TokenType::String(String::new()),
+                TokenType::String("${e}".to_string()),
+                TokenType::EOF,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_radix_and_exponent_literals() {
+        let mut scanner = Scanner::new("0xff 0B1010 1_000_000 1e3 2.5E-2 0x_1 1e".to_string());
+        scanner.scan_tokens();
+
+        let numbers: Vec<f64> = scanner
+            .tokens
+            .iter()
+            .filter_map(|t| match t.type_ {
+                TokenType::Number(n) => Some(n),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(numbers, vec![255.0, 10.0, 1_000_000.0, 1000.0, 0.025]);
+
+        let errors: Vec<&ScanError> = scanner
+            .errors
+            .iter()
+            .filter_map(|e| match e {
+                LoxError::Scan(err) => Some(&err.error),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                &ScanError::InvalidNumber("0x_1".to_string()),
+                &ScanError::InvalidNumber("1e".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tilde_slash_and_comments() {
+        let mut scanner = Scanner::new("a ~/ b; ~a\nif (x) // only when x\nx // 2".to_string());
+        let tokens = scanner.scan_tokens();
+
+        let expected_types = vec![
+            TokenType::Identifier,
+            TokenType::TildeSlash,
+            TokenType::Identifier,
+            TokenType::Semicolon,
+            TokenType::Tilde,
+            TokenType::Identifier,
+            TokenType::If,
+            TokenType::LeftParen,
+            TokenType::Identifier,
+            TokenType::RightParen,
+            TokenType::Identifier,
+            TokenType::EOF,
+        ];
+        let types: Vec<TokenType> = tokens.iter().map(|t| t.type_.clone()).collect();
+        assert_eq!(types, expected_types);
+    }
+
     #[test]
     fn test_number_literals() {
         let mut scanner = Scanner::new("123 456.789 0.5".to_string());
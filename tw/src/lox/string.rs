@@ -0,0 +1,239 @@
+use crate::{
+    errors::{Locate, RuntimeError},
+    lox::{
+        ast::{Arity, LiteralExpr, NativeFn, Object},
+        list::{list_index, slice_bound},
+        native::{NativeModule, number, string},
+        scanner::Scanner,
+    },
+};
+
+/**
+ * String methods are bound natives like the ones of lists (see `list_method`), they capture the
+ * string they were read from. Strings can't change, so a copy is as good as a reference.
+ *
+ * Lengths, indices and slices count characters, not bytes: `"héllo".len()` is 5 and
+ * `"héllo".charAt(1)` is `"é"`.
+ */
+pub fn string_method(text: String, name: &str) -> Option<NativeFn> {
+    let method = match name {
+        "len" => NativeFn::new("len", Arity::Exact(0), move |_, _| {
+            Ok(LiteralExpr::Number(text.chars().count() as f64))
+        }),
+        "charAt" => NativeFn::new("charAt", Arity::Exact(1), move |_, args| {
+            let chars: Vec<char> = text.chars().collect();
+            let i = list_index(&args[0], chars.len()).map_err(|e| e.at(0))?;
+
+            Ok(LiteralExpr::String(chars[i].to_string()))
+        }),
+        "slice" => NativeFn::new("slice", Arity::Range(1, 2), move |_, args| {
+            let chars: Vec<char> = text.chars().collect();
+            let len = chars.len();
+
+            let start = slice_bound(&args[0], len)?;
+            let end = match args.get(1) {
+                Some(end) => slice_bound(end, len)?,
+                None => len,
+            };
+
+            Ok(LiteralExpr::String(
+                chars[start..end.max(start)].iter().collect(),
+            ))
+        }),
+        "indexOf" => NativeFn::new("indexOf", Arity::Exact(1), move |_, args| {
+            let index = match text.find(string(&args[0])?) {
+                Some(byte) => text[..byte].chars().count() as f64,
+                None => -1.0,
+            };
+
+            Ok(LiteralExpr::Number(index))
+        }),
+        "contains" => NativeFn::new("contains", Arity::Exact(1), move |_, args| {
+            Ok(LiteralExpr::Boolean(text.contains(string(&args[0])?)))
+        }),
+        "startsWith" => NativeFn::new("startsWith", Arity::Exact(1), move |_, args| {
+            Ok(LiteralExpr::Boolean(text.starts_with(string(&args[0])?)))
+        }),
+        "endsWith" => NativeFn::new("endsWith", Arity::Exact(1), move |_, args| {
+            Ok(LiteralExpr::Boolean(text.ends_with(string(&args[0])?)))
+        }),
+        // An empty separator splits the string in characters
+        "split" => NativeFn::new("split", Arity::Exact(1), move |inter, args| {
+            let parts = match string(&args[0])? {
+                "" => text.chars().map(|c| c.to_string()).collect::<Vec<_>>(),
+                sep => text.split(sep).map(str::to_string).collect(),
+            };
+
+            let parts = parts.into_iter().map(LiteralExpr::String).collect();
+            Ok(inter.alloc(Object::List(parts)))
+        }),
+        // `", ".join(xs)`, the items don't need to be strings, they are joined as they print
+        "join" => NativeFn::new("join", Arity::Exact(1), move |inter, args| {
+            let LiteralExpr::List(list_id) = args[0] else {
+                return Err(RuntimeError::ListExpected.at(0));
+            };
+            let Object::List(items) = inter.heap[list_id].clone() else {
+                return Err(RuntimeError::ListExpected.at(0));
+            };
+
+            let mut parts = Vec::new();
+            for item in &items {
+                parts.push(inter.display(item, 0)?);
+            }
+
+            Ok(LiteralExpr::String(parts.join(&text)))
+        }),
+        "trim" => NativeFn::new("trim", Arity::Exact(0), move |_, _| {
+            Ok(LiteralExpr::String(text.trim().to_string()))
+        }),
+        "upper" => NativeFn::new("upper", Arity::Exact(0), move |_, _| {
+            Ok(LiteralExpr::String(text.to_uppercase()))
+        }),
+        "lower" => NativeFn::new("lower", Arity::Exact(0), move |_, _| {
+            Ok(LiteralExpr::String(text.to_lowercase()))
+        }),
+        "replace" => NativeFn::new("replace", Arity::Exact(2), move |_, args| {
+            let (from, to) = (string(&args[0])?, string(&args[1])?);
+
+            Ok(LiteralExpr::String(text.replace(from, to)))
+        }),
+        "ord" => NativeFn::new("ord", Arity::Exact(0), move |_, _| {
+            let mut chars = text.chars();
+            let (Some(c), None) = (chars.next(), chars.next()) else {
+                return Err(RuntimeError::CharacterExpected.at(0));
+            };
+
+            Ok(LiteralExpr::Number(c as u32 as f64))
+        }),
+        _ => return None,
+    };
+
+    Some(method)
+}
+
+/// Conversions between strings and other values, `str(x)` is the text `print x` would show
+pub struct StringModule;
+
+impl NativeModule for StringModule {
+    fn natives(&self) -> Vec<NativeFn> {
+        let str = NativeFn::new("str", Arity::Exact(1), |inter, args| {
+            Ok(LiteralExpr::String(inter.display(&args[0], 0)?))
+        });
+
+        let num = NativeFn::new("num", Arity::Exact(1), |_, args| match &args[0] {
+            LiteralExpr::Number(n) => Ok(LiteralExpr::Number(*n)),
+            LiteralExpr::String(s) => {
+                let text = s.trim();
+                let value = match text.strip_prefix('-') {
+                    Some(digits) => Scanner::number_literal(digits).map(|n| -n),
+                    None => Scanner::number_literal(text),
+                };
+
+                value
+                    .map(LiteralExpr::Number)
+                    .ok_or_else(|| RuntimeError::InvalidNumber(s.clone()).at(0))
+            }
+            _ => Err(RuntimeError::StringExpected.at(0)),
+        });
+
+        let chr = NativeFn::new("chr", Arity::Exact(1), |_, args| {
+            let code = number(&args[0])?;
+
+            let c = (code.fract() == 0.0 && code >= 0.0 && code <= u32::MAX as f64)
+                .then(|| char::from_u32(code as u32))
+                .flatten()
+                .ok_or_else(|| RuntimeError::InvalidCharCode(code).at(0))?;
+
+            Ok(LiteralExpr::String(c.to_string()))
+        });
+
+        vec![str, num, chr]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        errors::RuntimeError,
+        lox::{
+            Value,
+            test_util::{run, runtime_error},
+        },
+    };
+
+    #[test]
+    fn test_string_methods_count_characters() {
+        let (res, out) = run("
+            var s = \"héllo wörld\";
+            print s.len();
+            print s.charAt(1) + s.charAt(-1);
+            print s.slice(6);
+            print s.slice(0, -6);
+            print s.indexOf(\"wö\");
+            print s.indexOf(\"x\");
+            print \"😀\".ord();
+            print chr(233);
+        ");
+
+        assert!(res.is_ok(), "{res:?}");
+        assert_eq!(out, "11\néd\nwörld\nhéllo\n6\n-1\n128512\né\n");
+    }
+
+    #[test]
+    fn test_string_search_and_transform() {
+        let (res, out) = run("
+            var s = \"  a,b,,c  \";
+            print s.trim().split(\",\");
+            print \"abc\".split(\"\");
+            print \"-\".join([1, \"two\", nil]);
+            print s.contains(\"b,\") and s.trim().startsWith(\"a\") and s.endsWith(\" \");
+            print \"MiXed\".upper() + \"MiXed\".lower();
+            print \"a-b-c\".replace(\"-\", \"+\");
+        ");
+
+        assert!(res.is_ok(), "{res:?}");
+        assert_eq!(
+            out,
+            "[\"a\", \"b\", \"\", \"c\"]\n[\"a\", \"b\", \"c\"]\n1-two-nil\ntrue\nMIXEDmixed\na+b+c\n"
+        );
+    }
+
+    #[test]
+    fn test_string_conversions() {
+        let (res, _) = run("num(\" 1_000.5 \") + num(\"1e3\") + num(str(-2)) + num(\"0xff\");");
+        assert_eq!(res.unwrap(), Value::Number(2253.5));
+
+        for text in [
+            "inf", "infinity", "NaN", "1__0", "_1", "1_", "1e", ".5", "1 2", "--1", "",
+        ] {
+            assert_eq!(
+                runtime_error(&format!("num(\"{text}\");")),
+                RuntimeError::InvalidNumber(text.to_string())
+            );
+        }
+
+        let (res, _) = run("str([1, \"a\"]) + str(nil);");
+        assert_eq!(res.unwrap(), Value::String("[1, \"a\"]nil".to_string()));
+
+        assert_eq!(
+            runtime_error("num(\"12abc\");"),
+            RuntimeError::InvalidNumber("12abc".to_string())
+        );
+        assert_eq!(
+            runtime_error("chr(-1);"),
+            RuntimeError::InvalidCharCode(-1.0)
+        );
+        assert_eq!(
+            runtime_error("\"ab\".ord();"),
+            RuntimeError::CharacterExpected
+        );
+        assert_eq!(
+            runtime_error("\"ab\".charAt(2);"),
+            RuntimeError::IndexOutOfRange(2.0, 2)
+        );
+        assert_eq!(
+            runtime_error("\"ab\" - \"b\";"),
+            RuntimeError::NumberExpected
+        );
+    }
+}
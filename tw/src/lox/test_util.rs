@@ -0,0 +1,29 @@
+use crate::{
+    errors::{LoxError, RuntimeError},
+    lox::{Capture, Lox, Value},
+};
+
+/// Runs `src` in a new interpreter, returns its result and what it printed
+pub fn run(src: &str) -> (Result<Value, LoxError>, String) {
+    run_with(Lox::new(), src)
+}
+
+/// Same as `run` on an interpreter the test has set up, with permissions or arguments
+pub fn run_with(mut lox: Lox, src: &str) -> (Result<Value, LoxError>, String) {
+    let out = Capture::new();
+    lox.set_output(out.clone());
+
+    (lox.eval(src), out.contents())
+}
+
+/// Error `src` stops with, the test fails when it runs fine or fails before running
+pub fn runtime_error(src: &str) -> RuntimeError {
+    runtime_error_with(Lox::new(), src)
+}
+
+pub fn runtime_error_with(lox: Lox, src: &str) -> RuntimeError {
+    match run_with(lox, src).0 {
+        Err(LoxError::Runtime(err)) => err.error,
+        other => panic!("expected a runtime error, got {other:?}"),
+    }
+}
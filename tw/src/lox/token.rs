@@ -49,46 +49,66 @@ pub enum TokenType {
     RightParen,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
     Comma,
+    Colon,
     Dot,
     Minus,
     Plus,
     Semicolon,
     Slash,
     Star,
+    Ampersand,
+    Pipe,
+    Caret,
+    Tilde,
 
     // One or two character tokens.
     Bang,
     BangEqual,
     Equal,
     EqualEqual,
+    Arrow,
     Greater,
     GreaterEqual,
     Less,
     LessEqual,
+    LessLess,
+    GreaterGreater,
+    TildeSlash,
+    StarStar,
 
     // Literals.
     Identifier,
     String(String),
+    /// Part of a string before a `${`, the interpolated expression comes next
+    Interpolation(String),
     Number(f64),
 
     // Keywords.
     And,
+    Catch,
     Class,
     Else,
     False,
+    Finally,
     Fun,
     For,
     If,
+    Import,
     Nil,
     Or,
     Print,
     Return,
     Super,
     This,
+    Throw,
     True,
+    Try,
     Var,
     While,
+    Yield,
 
     EOF,
 }
@@ -116,10 +136,13 @@ impl PartialEq for TokenType {
         match (self, other) {
             (TokenType::Number(a), TokenType::Number(b)) => a.to_bits() == b.to_bits(),
             (TokenType::String(a), TokenType::String(b)) => a == b,
+            (TokenType::Interpolation(a), TokenType::Interpolation(b)) => a == b,
             (TokenType::Number(_), _) => false,
             (TokenType::String(_), _) => false,
+            (TokenType::Interpolation(_), _) => false,
             (_, TokenType::Number(_)) => false,
             (_, TokenType::String(_)) => false,
+            (_, TokenType::Interpolation(_)) => false,
             _ => std::mem::discriminant(self) == std::mem::discriminant(other),
         }
     }
@@ -132,7 +155,7 @@ impl std::hash::Hash for TokenType {
         std::mem::discriminant(self).hash(state);
         match self {
             TokenType::Number(n) => n.to_bits().hash(state),
-            TokenType::String(s) => s.hash(state),
+            TokenType::String(s) | TokenType::Interpolation(s) => s.hash(state),
             _ => {}
         }
     }
@@ -1,16 +1,19 @@
+use std::{fs, path::Path};
+
 use clap::Parser;
-use cli::{
-    alerts::Alert,
-    commands::{Cli, Commands, ToolCommand},
+use tw::{
+    cli::{
+        alerts::Alert,
+        commands::{Cli, Commands, ToolCommand},
+    },
+    errors::{IoError, LoxError},
+    lox::{Permissions, RunOptsCommand, handle_run_command},
+    tools::{
+        AstGenerator,
+        conform::{self, VM_COMPILE_ERROR_CODE, VM_RUNTIME_ERROR_CODE},
+    },
 };
-use tools::AstGenerator;
-
-use crate::lox::{RunOptsCommand, handle_run_command};
-
-mod cli;
-mod errors;
-mod lox;
-mod tools;
+use vm::exec::{ExecErr, VM};
 
 fn main() {
     let cli = Cli::parse();
@@ -18,15 +21,26 @@ fn main() {
     match &cli.command {
         Commands::Run {
             path,
+            file,
             debug,
             show_ast,
             show_tokens,
+            include,
+            allow_read,
+            allow_write,
+            args,
         } => handle_run_command(
-            path.to_owned(),
+            path.to_owned().or(file.to_owned()),
             RunOptsCommand {
                 debug: *debug,
                 show_ast: *show_ast,
                 show_tokens: *show_tokens,
+                include: include.to_owned(),
+                permissions: Permissions {
+                    read: *allow_read,
+                    write: *allow_write,
+                },
+                args: args.to_owned(),
             },
         ),
         Commands::Tool { command } => {
@@ -40,6 +54,8 @@ fn main() {
 fn handle_tool_command(tool_type: &ToolCommand) {
     match tool_type {
         ToolCommand::GenAst { output_path } => handle_gen_ast_tool(output_path),
+        ToolCommand::Conform { path } => handle_conform_tool(path),
+        ToolCommand::RunVm { path } => handle_run_vm_tool(path),
     }
 }
 
@@ -63,4 +79,40 @@ fn handle_gen_ast_tool(output_path: &String) {
     Alert::success(String::from("CLI | AST successfully created")).show();
 }
 
+fn handle_conform_tool(path: &str) {
+    let (total, divergences) = match conform::conform(Path::new(path)) {
+        Ok(res) => res,
+        Err(err) => err.report_and_exit(1),
+    };
+
+    for divergence in &divergences {
+        Alert::error(format!("CONFORM | {}", divergence.path.display())).show();
+        print!("{divergence}");
+    }
+
+    let summary = format!(
+        "CONFORM | {} of {total} files match between tw and vm",
+        total - divergences.len()
+    );
+
+    if divergences.is_empty() {
+        Alert::success(summary).show();
+    } else {
+        Alert::warning(summary).show_and_exit(1);
+    }
+}
+
+fn handle_run_vm_tool(path: &str) {
+    let source = match fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(..) => LoxError::Io(IoError::FileNotFound(path.to_string())).report_and_exit(1),
+    };
+
+    match VM::interpret(&source) {
+        Ok(()) => {}
+        Err(ExecErr::CompileErr) => std::process::exit(VM_COMPILE_ERROR_CODE),
+        Err(ExecErr::RuntimeErr) => std::process::exit(VM_RUNTIME_ERROR_CODE),
+    }
+}
+
 // endregion
@@ -0,0 +1,288 @@
+use std::{
+    env, fmt, fs,
+    path::{Path, PathBuf},
+    process::{Command, Output},
+};
+
+use crate::errors::{IoError, LoxError};
+
+// Exit codes used by `tool run-vm`, same as clox
+pub const VM_COMPILE_ERROR_CODE: i32 = 65;
+pub const VM_RUNTIME_ERROR_CODE: i32 = 70;
+
+/**
+ * The tree-walk interpreter and the bytecode VM report errors very differently (tw prefixes them
+ * with their kind, the VM only tells it with its exit code), and both of them may end the process
+ * on the first error.
+ *
+ * To compare them without touching any of that, each backend runs the script in a child process of
+ * this same binary, and only the program output and the *kind* of failure are compared. Messages and
+ * line numbers are left out on purpose, they are not expected to match between implementations.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Backend {
+    TreeWalk,
+    Vm,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Outcome {
+    Ok,
+    CompileError,
+    RuntimeError,
+    Crashed(Option<i32>),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct RunReport {
+    pub output: Vec<String>,
+    pub outcome: Outcome,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum DiffLine {
+    Same(String),
+    Removed(String),
+    Added(String),
+}
+
+pub struct Divergence {
+    pub path: PathBuf,
+    pub tw: RunReport,
+    pub vm: RunReport,
+}
+
+impl Backend {
+    fn args(&self, path: &Path) -> Vec<String> {
+        let path = path.to_string_lossy().to_string();
+
+        match self {
+            Backend::TreeWalk => vec!["run".to_string(), "--path".to_string(), path],
+            Backend::Vm => vec!["tool".to_string(), "run-vm".to_string(), path],
+        }
+    }
+
+    pub fn run(&self, path: &Path) -> Result<RunReport, LoxError> {
+        let exe = env::current_exe().map_err(IoError::Sys)?;
+        let output = Command::new(exe)
+            .args(self.args(path))
+            .output()
+            .map_err(IoError::Sys)?;
+
+        Ok(match self {
+            Backend::TreeWalk => RunReport::from_tw(&output),
+            Backend::Vm => RunReport::from_vm(&output),
+        })
+    }
+}
+
+impl RunReport {
+    fn from_tw(output: &Output) -> Self {
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+
+        // Diagnostics go to stderr as "RUNTIME | [line 1] ...", the first one tells the failure
+        let kind = stderr
+            .lines()
+            .find_map(|line| line.split_once(" | ").map(|(kind, _)| kind));
+        let outcome = match kind {
+            Some("RUNTIME") => Outcome::RuntimeError,
+            Some("SCAN" | "PARSE" | "SYS") => Outcome::CompileError,
+            _ if output.status.success() => Outcome::Ok,
+            _ => Outcome::Crashed(output.status.code()),
+        };
+
+        Self {
+            output: stdout.lines().map(|l| l.to_string()).collect(),
+            outcome,
+        }
+    }
+
+    fn from_vm(output: &Output) -> Self {
+        let stdout = String::from_utf8_lossy(&output.stdout);
+
+        let outcome = match output.status.code() {
+            Some(0) => Outcome::Ok,
+            Some(VM_COMPILE_ERROR_CODE) => Outcome::CompileError,
+            Some(VM_RUNTIME_ERROR_CODE) => Outcome::RuntimeError,
+            code => Outcome::Crashed(code),
+        };
+
+        Self {
+            output: stdout.lines().map(|l| l.to_string()).collect(),
+            outcome,
+        }
+    }
+}
+
+/// Runs every `.lox` file in `path` (a file or a directory) on both backends
+pub fn conform(path: &Path) -> Result<(usize, Vec<Divergence>), LoxError> {
+    let files = collect_scripts(path)?;
+    let mut divergences = Vec::new();
+
+    for file in &files {
+        let tw = Backend::TreeWalk.run(file)?;
+        let vm = Backend::Vm.run(file)?;
+
+        if tw != vm {
+            divergences.push(Divergence {
+                path: file.clone(),
+                tw,
+                vm,
+            });
+        }
+    }
+
+    Ok((files.len(), divergences))
+}
+
+fn collect_scripts(path: &Path) -> Result<Vec<PathBuf>, LoxError> {
+    if !path.exists() {
+        return Err(IoError::FileNotFound(path.to_string_lossy().to_string()).into());
+    }
+
+    if path.is_file() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+
+    let mut files = Vec::new();
+    for entry in fs::read_dir(path).map_err(IoError::Sys)? {
+        let entry_path = entry.map_err(IoError::Sys)?.path();
+
+        if entry_path.is_dir() {
+            files.extend(collect_scripts(&entry_path)?);
+        } else if entry_path.extension().is_some_and(|ext| ext == "lox") {
+            files.push(entry_path);
+        }
+    }
+    files.sort();
+
+    Ok(files)
+}
+
+/// Line diff based on the longest common subsequence of both outputs
+pub fn diff_lines(old: &[String], new: &[String]) -> Vec<DiffLine> {
+    let (n, m) = (old.len(), new.len());
+
+    // lcs[i][j] is the LCS length of old[i..] and new[j..]
+    let mut lcs = vec![vec![0usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut diff = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if old[i] == new[j] {
+            diff.push(DiffLine::Same(old[i].clone()));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            diff.push(DiffLine::Removed(old[i].clone()));
+            i += 1;
+        } else {
+            diff.push(DiffLine::Added(new[j].clone()));
+            j += 1;
+        }
+    }
+
+    diff.extend(old[i..].iter().cloned().map(DiffLine::Removed));
+    diff.extend(new[j..].iter().cloned().map(DiffLine::Added));
+
+    diff
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Outcome::Ok => write!(f, "ok"),
+            Outcome::CompileError => write!(f, "compile error"),
+            Outcome::RuntimeError => write!(f, "runtime error"),
+            Outcome::Crashed(Some(code)) => write!(f, "crashed (exit code {code})"),
+            Outcome::Crashed(None) => write!(f, "crashed"),
+        }
+    }
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.tw.outcome != self.vm.outcome {
+            writeln!(
+                f,
+                "  outcome: tw {} | vm {}",
+                self.tw.outcome, self.vm.outcome
+            )?;
+        }
+
+        let (mut tw_line, mut vm_line) = (0, 0);
+        for diff_line in diff_lines(&self.tw.output, &self.vm.output) {
+            match diff_line {
+                DiffLine::Same(_) => {
+                    tw_line += 1;
+                    vm_line += 1;
+                }
+                DiffLine::Removed(text) => {
+                    tw_line += 1;
+                    writeln!(f, "  {tw_line:>4} - tw | {text}")?;
+                }
+                DiffLine::Added(text) => {
+                    vm_line += 1;
+                    writeln!(f, "  {vm_line:>4} + vm | {text}")?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(src: &[&str]) -> Vec<String> {
+        src.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_diff_identical_outputs() {
+        let out = lines(&["1", "2", "3"]);
+        let diff = diff_lines(&out, &out);
+
+        assert!(diff.iter().all(|d| matches!(d, DiffLine::Same(_))));
+    }
+
+    #[test]
+    fn test_diff_changed_line() {
+        let diff = diff_lines(&lines(&["1", "true", "3"]), &lines(&["1", "false", "3"]));
+
+        assert_eq!(
+            diff,
+            vec![
+                DiffLine::Same("1".to_string()),
+                DiffLine::Removed("true".to_string()),
+                DiffLine::Added("false".to_string()),
+                DiffLine::Same("3".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_missing_tail() {
+        let diff = diff_lines(&lines(&["a", "b"]), &lines(&["a"]));
+
+        assert_eq!(
+            diff,
+            vec![
+                DiffLine::Same("a".to_string()),
+                DiffLine::Removed("b".to_string())
+            ]
+        );
+    }
+}
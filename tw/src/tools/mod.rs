@@ -1,5 +1,7 @@
 use std::{fs::File, io::Write};
 
+pub mod conform;
+
 use crate::{
     errors::{IoError, LoxError},
     lox::ast::{Expr, Stmt},
@@ -24,6 +24,8 @@ pub enum OpCode {
     Dup,
 
     Print,
+    /// Replaces the top of the stack with the string `print` would write for it
+    ToStr,
     DefGlob,
     GetGlob,
     GetLocal,
@@ -45,6 +47,16 @@ pub enum OpCode {
     Mul,
     Div,
     Mod,
+    IntDiv,
+    Pow,
+
+    // Bitwise Op
+    BitAnd,
+    BitOr,
+    BitXor,
+    BitNot,
+    Shl,
+    Shr,
 
     // Logic Op
     Not,
@@ -52,6 +64,14 @@ pub enum OpCode {
     Greater,
     Less,
 
+    // Array Op
+    BuildList,
+    GetIndex,
+    SetIndex,
+
+    GetProp,
+
+    Call,
     Return,
     _COUNT,
 }
@@ -1,8 +1,8 @@
 use crate::{
-    scanner::Scanner,
-    values::{ArithOp, Constant},
+    scanner::{parse_number, Scanner},
+    values::{ArithOp, Constant, Function},
 };
-use std::{collections::HashMap, str::FromStr, u8};
+use std::{collections::HashMap, mem, rc::Rc, u8};
 
 use crate::{
     chunk::{Byte, Chunk, OpCode},
@@ -16,7 +16,9 @@ enum PrefixRule {
     Number,
     Literal,
     String,
+    Interpolation,
     Variable,
+    List,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -24,6 +26,9 @@ enum InfixRule {
     Binary,
     And,
     Or,
+    Call,
+    Index,
+    Dot,
 }
 
 struct ParseRule {
@@ -41,10 +46,15 @@ enum Precedence {
     And,
     Equality,
     Comparison,
+    BitOr,
+    BitXor,
+    BitAnd,
+    Shift,
     Term,
     Factor,
     Unary,
-    _Call,
+    Power,
+    Call,
     Primary,
 }
 
@@ -79,15 +89,39 @@ struct Local {
     ready: bool,
 }
 
+#[derive(Default, PartialEq)]
+enum FunctionKind {
+    #[default]
+    Script,
+    Function,
+}
+
+/// Locals and scope depth of the function being compiled, the top-level code is a function too
 #[derive(Default)]
 struct CompilerContext {
     locals: Vec<Local>,
     scope: usize,
+    kind: FunctionKind,
 }
 
 impl CompilerContext {
     const MAX_LOCALS: usize = (u8::MAX as usize) + 1;
 
+    /// Slot 0 of a call frame holds the function being called, the local has no name so it can't be used
+    fn function() -> Self {
+        let callee = Local {
+            name: Token::default(),
+            depth: 0,
+            ready: true,
+        };
+
+        Self {
+            locals: vec![callee],
+            scope: 0,
+            kind: FunctionKind::Function,
+        }
+    }
+
     fn add_local(&mut self, name: Token) -> Result<(), &'static str> {
         if self.locals.len() == Self::MAX_LOCALS {
             return Err("Too many local variables in function.");
@@ -132,6 +166,9 @@ pub struct Compiler<'a> {
     chunk: &'a mut Chunk,
     context: CompilerContext,
     source: &'a str,
+
+    /// Compile errors in the order they were found, the host decides where to report them
+    pub errors: Vec<String>,
 }
 
 impl<'a> Compiler<'a> {
@@ -147,6 +184,7 @@ impl<'a> Compiler<'a> {
             source,
             chunk,
             context: CompilerContext::default(),
+            errors: Vec::new(),
         }
     }
 
@@ -156,13 +194,15 @@ impl<'a> Compiler<'a> {
         while !self._match(TokenKind::EOF) {
             self.declaration();
         }
-        self.emit_byte(OpCode::Return);
+        self.emit_return();
 
         !self.parser.had_err
     }
 
     fn declaration(&mut self) {
-        if self._match(TokenKind::Var) {
+        if self._match(TokenKind::Fun) {
+            self.fun_decl();
+        } else if self._match(TokenKind::Var) {
             self.var_decl();
         } else {
             self.statement();
@@ -173,8 +213,60 @@ impl<'a> Compiler<'a> {
         }
     }
 
+    fn fun_decl(&mut self) {
+        let var = self.parse_var("Expect function name.");
+        let name = self.parser.prev;
+
+        self.function(name);
+        self.def_var(var);
+    }
+
+    /**
+     * The body goes to a chunk of its own. The chunk and the locals of the enclosing function are set
+     * aside while it's compiled and the finished function is stored as a constant of the enclosing chunk.
+     */
+    fn function(&mut self, name: Token) {
+        let enclosing_chunk = mem::replace(self.chunk, Chunk::new());
+        let enclosing_context = mem::replace(&mut self.context, CompilerContext::function());
+        self.context.begin_scope();
+
+        self.consume(TokenKind::LeftParen, "Expect '(' after function name.");
+        let mut arity: usize = 0;
+        if !self.check(TokenKind::RightParen) {
+            loop {
+                if arity == u8::MAX as usize {
+                    self.error_at(self.parser.curr, "Can't have more than 255 parameters.");
+                }
+                arity += 1;
+
+                let param = self.parse_var("Expect parameter name.");
+                self.def_var(param);
+
+                if !self._match(TokenKind::Comma) {
+                    break;
+                }
+            }
+        }
+        self.consume(TokenKind::RightParen, "Expect ')' after parameters.");
+
+        self.consume(TokenKind::LeftBrace, "Expect '{' before function body.");
+        self.block();
+        self.emit_return();
+
+        let chunk = mem::replace(self.chunk, enclosing_chunk);
+        self.context = enclosing_context;
+
+        let function = Function {
+            name: name.lexeme(self.source).into(),
+            arity: arity.min(u8::MAX as usize) as u8,
+            chunk,
+        };
+        let const_ = self.make_constant(Constant::Function(Rc::new(function)));
+        self.emit_bytes(OpCode::Cons as u8, const_);
+    }
+
     fn var_decl(&mut self) {
-        let var = self.parse_var();
+        let var = self.parse_var("Expect variable name after 'var' keyword.");
 
         if self.match_any(&[
             TokenKind::PlusEqual,
@@ -211,11 +303,8 @@ impl<'a> Compiler<'a> {
         self.emit_bytes(OpCode::DefGlob as u8, var);
     }
 
-    fn parse_var(&mut self) -> Byte {
-        self.consume(
-            TokenKind::Identifier,
-            "Expect variable name after 'var' keyword.",
-        );
+    fn parse_var(&mut self, error: &str) -> Byte {
+        self.consume(TokenKind::Identifier, error);
 
         let name = self.parser.prev.span;
         self.declare_var();
@@ -223,10 +312,8 @@ impl<'a> Compiler<'a> {
             return 0;
         }
 
-        self.make_constant(Constant::String {
-            start: name.start,
-            end: name.end,
-        })
+        let name = self.source[name.start..name.end].into();
+        self.make_constant(Constant::String(name))
     }
 
     fn declare_var(&mut self) {
@@ -265,10 +352,8 @@ impl<'a> Compiler<'a> {
         let (arg, set_op, get_op) = if let Some(idx) = self.resolve_local(var) {
             (idx, OpCode::SetLocal as u8, OpCode::GetLocal as u8)
         } else {
-            let glob = self.make_constant(Constant::String {
-                start: var.span.start,
-                end: var.span.end,
-            });
+            let name = var.lexeme(self.source).into();
+            let glob = self.make_constant(Constant::String(name));
 
             (glob, OpCode::SetGlob as u8, OpCode::GetGlob as u8)
         };
@@ -338,10 +423,27 @@ impl<'a> Compiler<'a> {
             TokenKind::While => self.while_stmt(),
             TokenKind::For => self.for_stmt(),
             TokenKind::Switch => self.switch_stmt(),
+            TokenKind::Return => self.return_stmt(),
             _ => self.expression_stmt(),
         }
     }
 
+    fn return_stmt(&mut self) {
+        self.advance(); // Consume 'return'
+
+        if self.context.kind == FunctionKind::Script {
+            self.error_at(self.parser.prev, "Can't return from top-level code.");
+        }
+
+        if self._match(TokenKind::Semicolon) {
+            self.emit_return();
+        } else {
+            self.expression();
+            self.consume(TokenKind::Semicolon, "Expect ';' after return value.");
+            self.emit_byte(OpCode::Return);
+        }
+    }
+
     fn switch_stmt(&mut self) {
         self.advance(); // Consume 'switch'
 
@@ -528,7 +630,7 @@ impl<'a> Compiler<'a> {
     fn number(&mut self) {
         let str = self.parser.prev.lexeme(self.source);
         // The scanner has the job of ensuring that the lexeme is a number
-        let val = f64::from_str(str).unwrap();
+        let val = parse_number(str).unwrap();
         let const_ = self.make_constant(Constant::Number(val));
 
         self.emit_bytes(OpCode::Cons as u8, const_);
@@ -541,19 +643,45 @@ impl<'a> Compiler<'a> {
         match op {
             TokenKind::Minus => self.emit_byte(OpCode::Neg),
             TokenKind::Bang => self.emit_byte(OpCode::Not),
+            TokenKind::Tilde => self.emit_byte(OpCode::BitNot),
             _ => {}
         };
     }
 
     fn string(&mut self) {
-        let const_ = self.make_constant(Constant::String {
-            start: self.parser.prev.span.start + 1,
-            end: self.parser.prev.span.end - 1,
-        });
+        let value = self.parser.prev.string_value(self.source);
+        let const_ = self.make_constant(Constant::String(value.into()));
 
         self.emit_bytes(OpCode::Cons as u8, const_);
     }
 
+    /// `"a ${b} c"` compiles to `"a " + str(b) + " c"`, the scanner splits the string around each `${...}`
+    fn interpolation(&mut self) {
+        self.string();
+
+        loop {
+            self.expression();
+            self.emit_byte(OpCode::ToStr);
+            self.emit_byte(OpCode::Add);
+
+            if !self.check_any(&[TokenKind::String, TokenKind::Interpolation]) {
+                self.error_at(
+                    self.parser.curr,
+                    "Expect '}' after interpolated expression.",
+                );
+                return;
+            }
+            self.advance();
+
+            self.string();
+            self.emit_byte(OpCode::Add);
+
+            if self.parser.prev.kind == TokenKind::String {
+                break;
+            }
+        }
+    }
+
     fn literal(&mut self) {
         match self.parser.prev.kind {
             TokenKind::False => self.emit_byte(OpCode::False),
@@ -574,6 +702,81 @@ impl<'a> Compiler<'a> {
         self.path_jump(jump_right);
     }
 
+    fn call(&mut self) {
+        let argc = self.argument_list();
+        self.emit_bytes(OpCode::Call as u8, argc);
+    }
+
+    fn argument_list(&mut self) -> u8 {
+        let mut argc: usize = 0;
+
+        if !self.check(TokenKind::RightParen) {
+            loop {
+                self.expression();
+
+                if argc == u8::MAX as usize {
+                    self.error("Can't have more than 255 arguments.");
+                }
+                argc += 1;
+
+                if !self._match(TokenKind::Comma) {
+                    break;
+                }
+            }
+        }
+
+        self.consume(TokenKind::RightParen, "Expect ')' after arguments.");
+
+        argc.min(u8::MAX as usize) as u8
+    }
+
+    fn list(&mut self) {
+        let mut count: usize = 0;
+
+        if !self.check(TokenKind::RightBracket) {
+            loop {
+                self.expression();
+
+                if count == u8::MAX as usize {
+                    self.error("Can't have more than 255 elements in an array literal.");
+                }
+                count += 1;
+
+                // A trailing comma is allowed
+                if !self._match(TokenKind::Comma) || self.check(TokenKind::RightBracket) {
+                    break;
+                }
+            }
+        }
+
+        self.consume(TokenKind::RightBracket, "Expect ']' after array elements.");
+
+        let count = count.min(u8::MAX as usize) as u8;
+        self.emit_bytes(OpCode::BuildList as u8, count);
+    }
+
+    /// `arr[i]` or, when it's an assignment target, `arr[i] = value`
+    fn index(&mut self) {
+        self.expression();
+        self.consume(TokenKind::RightBracket, "Expect ']' after index.");
+
+        if self.parser.can_assign && self._match(TokenKind::Equal) {
+            self.expression();
+            self.emit_byte(OpCode::SetIndex);
+        } else {
+            self.emit_byte(OpCode::GetIndex);
+        }
+    }
+
+    /// `value.name`, properties are read only
+    fn dot(&mut self) {
+        self.consume(TokenKind::Identifier, "Expect property name after '.'.");
+        let name = self.parser.prev.lexeme(self.source).into();
+        let name = self.make_constant(Constant::String(name));
+
+        self.emit_bytes(OpCode::GetProp as u8, name);
+    }
+
     fn and(&mut self) {
         let jump_right = self.emit_jump(OpCode::JumpIfFalse);
         self.emit_byte(OpCode::Pop);
@@ -585,7 +788,13 @@ impl<'a> Compiler<'a> {
     fn binary(&mut self) {
         let op = self.parser.prev.kind;
         let precedence = self.parser_rule_from(&op).precedence;
-        self.parse_precedence(precedence.next());
+
+        // `**` is right associative and its right operand can be a unary: `2 ** -1`
+        if op == TokenKind::StarStar {
+            self.parse_precedence(Precedence::Unary);
+        } else {
+            self.parse_precedence(precedence.next());
+        }
 
         match op {
             TokenKind::BangEqual => self.emit_bytes(OpCode::Eq, OpCode::Not),
@@ -600,6 +809,14 @@ impl<'a> Compiler<'a> {
             TokenKind::Star => self.emit_byte(OpCode::Mul),
             TokenKind::Slash => self.emit_byte(OpCode::Div),
             TokenKind::Percent => self.emit_byte(OpCode::Mod),
+            TokenKind::TildeSlash => self.emit_byte(OpCode::IntDiv),
+            TokenKind::StarStar => self.emit_byte(OpCode::Pow),
+
+            TokenKind::Ampersand => self.emit_byte(OpCode::BitAnd),
+            TokenKind::Pipe => self.emit_byte(OpCode::BitOr),
+            TokenKind::Caret => self.emit_byte(OpCode::BitXor),
+            TokenKind::LessLess => self.emit_byte(OpCode::Shl),
+            TokenKind::GreaterGreater => self.emit_byte(OpCode::Shr),
             _ => {}
         }
     }
@@ -662,6 +879,8 @@ impl<'a> Compiler<'a> {
             PrefixRule::Unary => self.unary(),
             PrefixRule::Literal => self.literal(),
             PrefixRule::String => self.string(),
+            PrefixRule::Interpolation => self.interpolation(),
+            PrefixRule::List => self.list(),
         }
     }
 
@@ -670,6 +889,9 @@ impl<'a> Compiler<'a> {
             InfixRule::Binary => self.binary(),
             InfixRule::And => self.and(),
             InfixRule::Or => self.or(),
+            InfixRule::Call => self.call(),
+            InfixRule::Index => self.index(),
+            InfixRule::Dot => self.dot(),
         }
     }
 
@@ -783,6 +1005,11 @@ impl<'a> Compiler<'a> {
         self.chunk.code.len() - 2
     }
 
+    /// Functions without a `return` give back nil
+    fn emit_return(&mut self) {
+        self.emit_bytes(OpCode::Nil, OpCode::Return);
+    }
+
     fn emit_n_bytes(&mut self, n: usize, byte: Byte) {
         for _ in 0..n {
             self.emit_byte(byte);
@@ -813,7 +1040,7 @@ impl<'a> Compiler<'a> {
         }
         self.parser.panic_mode = true;
 
-        eprintln!("{msg}");
+        self.errors.push(msg.to_string());
 
         self.parser.had_err = true;
     }
@@ -851,7 +1078,25 @@ impl Parser {
     fn define_rules(&mut self) {
         self.rules.insert(
             TokenKind::LeftParen,
-            ParseRule::default().prefix(PrefixRule::Grouping),
+            ParseRule::default()
+                .prefix(PrefixRule::Grouping)
+                .infix(InfixRule::Call)
+                .precedence(Precedence::Call),
+        );
+
+        self.rules.insert(
+            TokenKind::LeftBracket,
+            ParseRule::default()
+                .prefix(PrefixRule::List)
+                .infix(InfixRule::Index)
+                .precedence(Precedence::Call),
+        );
+
+        self.rules.insert(
+            TokenKind::Dot,
+            ParseRule::default()
+                .infix(InfixRule::Dot)
+                .precedence(Precedence::Call),
         );
 
         self.rules.insert(
@@ -887,6 +1132,53 @@ impl Parser {
                 .infix(InfixRule::Binary)
                 .precedence(Precedence::Factor),
         );
+        self.rules.insert(
+            TokenKind::TildeSlash,
+            ParseRule::default()
+                .infix(InfixRule::Binary)
+                .precedence(Precedence::Factor),
+        );
+        self.rules.insert(
+            TokenKind::StarStar,
+            ParseRule::default()
+                .infix(InfixRule::Binary)
+                .precedence(Precedence::Power),
+        );
+
+        self.rules.insert(
+            TokenKind::Ampersand,
+            ParseRule::default()
+                .infix(InfixRule::Binary)
+                .precedence(Precedence::BitAnd),
+        );
+        self.rules.insert(
+            TokenKind::Pipe,
+            ParseRule::default()
+                .infix(InfixRule::Binary)
+                .precedence(Precedence::BitOr),
+        );
+        self.rules.insert(
+            TokenKind::Caret,
+            ParseRule::default()
+                .infix(InfixRule::Binary)
+                .precedence(Precedence::BitXor),
+        );
+        self.rules.insert(
+            TokenKind::LessLess,
+            ParseRule::default()
+                .infix(InfixRule::Binary)
+                .precedence(Precedence::Shift),
+        );
+        self.rules.insert(
+            TokenKind::GreaterGreater,
+            ParseRule::default()
+                .infix(InfixRule::Binary)
+                .precedence(Precedence::Shift),
+        );
+        self.rules.insert(
+            TokenKind::Tilde,
+            ParseRule::default().prefix(PrefixRule::Unary),
+        );
 
         self.rules.insert(
             TokenKind::Number,
@@ -951,6 +1243,11 @@ impl Parser {
             ParseRule::default().prefix(PrefixRule::String),
         );
 
+        self.rules.insert(
+            TokenKind::Interpolation,
+            ParseRule::default().prefix(PrefixRule::Interpolation),
+        );
+
         self.rules.insert(
             TokenKind::Identifier,
             ParseRule::default().prefix(PrefixRule::Variable),
@@ -981,11 +1278,16 @@ impl Precedence {
             Precedence::Or => Precedence::And,
             Precedence::And => Precedence::Equality,
             Precedence::Equality => Precedence::Comparison,
-            Precedence::Comparison => Precedence::Term,
+            Precedence::Comparison => Precedence::BitOr,
+            Precedence::BitOr => Precedence::BitXor,
+            Precedence::BitXor => Precedence::BitAnd,
+            Precedence::BitAnd => Precedence::Shift,
+            Precedence::Shift => Precedence::Term,
             Precedence::Term => Precedence::Factor,
             Precedence::Factor => Precedence::Unary,
-            Precedence::Unary => Precedence::_Call,
-            Precedence::_Call => Precedence::Primary,
+            Precedence::Unary => Precedence::Power,
+            Precedence::Power => Precedence::Call,
+            Precedence::Call => Precedence::Primary,
             Precedence::Primary => Precedence::Primary,
         }
     }
@@ -0,0 +1,86 @@
+use crate::{exec::VM, values::Value};
+
+/// Rust values a host can hand to the VM (globals, native return values)
+pub trait IntoValue {
+    fn into_value(self, vm: &mut VM) -> Value;
+}
+
+/// Rust values a host can read back from the VM, `None` when the types don't match
+pub trait FromValue: Sized {
+    fn from_value(value: Value, vm: &VM) -> Option<Self>;
+}
+
+impl IntoValue for Value {
+    fn into_value(self, _: &mut VM) -> Value {
+        self
+    }
+}
+
+impl IntoValue for f64 {
+    fn into_value(self, _: &mut VM) -> Value {
+        Value::Number(self)
+    }
+}
+
+impl IntoValue for bool {
+    fn into_value(self, _: &mut VM) -> Value {
+        Value::Boolean(self)
+    }
+}
+
+impl IntoValue for () {
+    fn into_value(self, _: &mut VM) -> Value {
+        Value::Nil
+    }
+}
+
+impl IntoValue for &str {
+    fn into_value(self, vm: &mut VM) -> Value {
+        vm.alloc_string(self)
+    }
+}
+
+impl IntoValue for String {
+    fn into_value(self, vm: &mut VM) -> Value {
+        vm.alloc_string(&self)
+    }
+}
+
+impl<T: IntoValue> IntoValue for Option<T> {
+    fn into_value(self, vm: &mut VM) -> Value {
+        match self {
+            Some(v) => v.into_value(vm),
+            None => Value::Nil,
+        }
+    }
+}
+
+impl FromValue for Value {
+    fn from_value(value: Value, _: &VM) -> Option<Self> {
+        Some(value)
+    }
+}
+
+impl FromValue for f64 {
+    fn from_value(value: Value, _: &VM) -> Option<Self> {
+        match value {
+            Value::Number(n) => Some(n),
+            _ => None,
+        }
+    }
+}
+
+impl FromValue for bool {
+    fn from_value(value: Value, _: &VM) -> Option<Self> {
+        match value {
+            Value::Boolean(b) => Some(b),
+            _ => None,
+        }
+    }
+}
+
+impl FromValue for String {
+    fn from_value(value: Value, vm: &VM) -> Option<Self> {
+        vm.as_str(value).map(|s| s.to_string())
+    }
+}
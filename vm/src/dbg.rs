@@ -1,6 +1,6 @@
 use crate::chunk::{Chunk, OpCode};
 use crate::scanner::Token;
-use crate::values::{Object, Value};
+use crate::values::{fmt_value, Object, Value};
 
 enum JumpDir {
     Back,
@@ -35,12 +35,14 @@ pub fn disasm_instr(offset: usize, chunk: &Chunk) -> usize {
     }
 
     match opcode {
+        OpCode::Call => byte_instr("Call", offset, chunk),
         OpCode::Return => simple_instr("Return", offset),
         OpCode::Cons => const_instr("Constant", offset, chunk),
         OpCode::Pop => simple_instr("Pop", offset),
         OpCode::Dup => simple_instr("Dup", offset),
 
         OpCode::Print => simple_instr("Print", offset),
+        OpCode::ToStr => simple_instr("ToStr", offset),
         OpCode::DefGlob => const_instr("DefGlob", offset, chunk),
         OpCode::GetGlob => const_instr("GetGlob", offset, chunk),
         OpCode::GetLocal => byte_instr("GetLocal", offset, chunk),
@@ -65,6 +67,20 @@ pub fn disasm_instr(offset: usize, chunk: &Chunk) -> usize {
         OpCode::Mul => simple_instr("Mul", offset),
         OpCode::Div => simple_instr("Div", offset),
         OpCode::Mod => simple_instr("Mod", offset),
+        OpCode::IntDiv => simple_instr("IntDiv", offset),
+        OpCode::Pow => simple_instr("Pow", offset),
+
+        OpCode::BitAnd => simple_instr("BitAnd", offset),
+        OpCode::BitOr => simple_instr("BitOr", offset),
+        OpCode::BitXor => simple_instr("BitXor", offset),
+        OpCode::BitNot => simple_instr("BitNot", offset),
+        OpCode::Shl => simple_instr("Shl", offset),
+        OpCode::Shr => simple_instr("Shr", offset),
+
+        OpCode::BuildList => byte_instr("BuildList", offset, chunk),
+        OpCode::GetIndex => simple_instr("GetIndex", offset),
+        OpCode::SetIndex => simple_instr("SetIndex", offset),
+        OpCode::GetProp => const_instr("GetProp", offset, chunk),
         // This should never happen
         OpCode::_COUNT => panic!(),
     }
@@ -111,11 +127,7 @@ fn const_instr(name: &'static str, mut offset: usize, chunk: &Chunk) -> usize {
 
 pub fn dbg_mem(stack: &Vec<Value>, heap: &Vec<Object>) {
     for slot in stack.iter() {
-        if let Value::Object(id) = slot {
-            print!("[ {} ]", heap[id.0])
-        } else {
-            print!("[ {slot} ]");
-        }
+        print!("[ {} ]", fmt_value(*slot, heap));
     }
     println!();
 }
@@ -1,4 +1,10 @@
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    io::{self, Write},
+    mem,
+    rc::Rc,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 use super::chunk::{Chunk, OpCode};
 
@@ -7,11 +13,14 @@ use super::dbg::{dbg_mem, disasm_instr};
 
 use crate::{
     compiler::Compiler,
-    scanner::Span,
-    values::{ArithOp, ArithmeticError, CompareOp, Constant, ObjRef, Object, StrObj, Value},
+    convert::{FromValue, IntoValue},
+    values::{
+        fmt_value, integer, ArithOp, ArithmeticError, ArrObj, Builtin, CallFrame, CompareOp,
+        Constant, FiberObj, Function, NativeFn, NativeObj, NsObj, ObjRef, Object, StrObj, Value,
+    },
 };
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub enum ExecErr {
     CompileErr,
     RuntimeErr,
@@ -51,46 +60,216 @@ impl<T> Interner<T> {
     }
 }
 
-pub struct VM<'a> {
-    pub chunk: Chunk,
+pub struct VM {
+    pub frames: Vec<CallFrame>,
     pub stack: Vec<Value>,
     pub heap: Vec<Object>,
-    pub src: &'a str,
-    pub ip: usize,
+    /// Fiber that owns `stack` and `frames` while it runs
+    pub fiber: ObjRef,
 
     pub strings: Interner<ObjRef>,
     pub globals: Interner<Value>,
+
+    out: Box<dyn Write>,
+    err: Box<dyn Write>,
+}
+
+fn clock(_: &mut VM, _: &[Value]) -> Result<Value, String> {
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| e.to_string())?
+        .as_secs_f64();
+
+    Ok(Value::Number(time))
 }
 
-impl<'a> VM<'a> {
+/// Number of items of an array or characters of a string
+fn len(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    if let Some(s) = vm.as_str(args[0]) {
+        return Ok(Value::Number(s.chars().count() as f64));
+    }
+
+    match vm.as_array(args[0]) {
+        Some(items) => Ok(Value::Number(items.len() as f64)),
+        None => Err("Can only get the length of arrays and strings.".to_string()),
+    }
+}
+
+fn append(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let items = vm
+        .as_array(args[0])
+        .ok_or_else(|| "Can only append to arrays.".to_string())?;
+    items.push(args[1]);
+
+    Ok(Value::Nil)
+}
+
+impl Default for VM {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VM {
+    const FRAMES_MAX: usize = 256;
+
+    pub fn new() -> Self {
+        let mut vm = VM {
+            frames: Vec::new(),
+            fiber: ObjRef(0),
+            stack: Vec::new(),
+            heap: Vec::new(),
+            strings: Interner::new(),
+            globals: Interner::new(),
+            out: Box::new(io::stdout()),
+            err: Box::new(io::stderr()),
+        };
+
+        vm.define_native("clock", 0, clock);
+        vm.define_native("len", 1, len);
+        vm.define_native("append", 2, append);
+        vm.define_fiber();
+
+        vm
+    }
+
+    /// Compiles and runs `source` in a new VM
     pub fn interpret(source: &str) -> ExecResult {
+        let mut vm = VM::new();
+
+        vm.load(source)?;
+        vm.run()
+    }
+
+    /// Compiles `source`, replacing the previous chunk. Globals and heap are kept between loads
+    pub fn load(&mut self, source: &str) -> ExecResult {
         let mut chunk = Chunk::new();
         let mut c = Compiler::new(source, &mut chunk);
 
         if !c.compile() {
+            for msg in &c.errors {
+                let _ = writeln!(self.err, "{msg}");
+            }
+
             return Err(ExecErr::CompileErr);
         }
 
-        let mut vm = VM {
+        let script = Function {
+            name: "".into(),
+            arity: 0,
             chunk,
+        };
+        self.stack.clear();
+        self.frames = vec![CallFrame {
+            function: Rc::new(script),
             ip: 0,
+            slots: 0,
+        }];
+        self.fiber = self.allocate_obj(Object::Fiber(FiberObj {
             stack: Vec::new(),
-            heap: Vec::new(),
-            strings: Interner::new(),
-            globals: Interner::new(),
-            src: source,
-        };
+            frames: Vec::new(),
+            caller: None,
+            fresh: false,
+            call: None,
+        }));
 
-        vm.run()
+        Ok(())
     }
 
-    fn run(&mut self) -> ExecResult {
+    pub fn define_native(&mut self, name: &str, arity: u8, function: NativeFn) {
+        let native = Object::Native(NativeObj {
+            name: name.into(),
+            arity,
+            function,
+        });
+
+        let native_ref = self.allocate_obj(native);
+        self.globals.set(name, Value::Object(native_ref));
+    }
+
+    /// `Fiber.new` and `Fiber.yield`, grouped under the `Fiber` global
+    fn define_fiber(&mut self) {
+        let mut members = HashMap::new();
+        for builtin in [Builtin::FiberNew, Builtin::FiberYield] {
+            let builtin_ref = self.allocate_obj(Object::Builtin(builtin));
+            members.insert(builtin.name().into(), Value::Object(builtin_ref));
+        }
+
+        let fiber = Object::Namespace(NsObj {
+            name: "Fiber".into(),
+            members,
+        });
+        let fiber_ref = self.allocate_obj(fiber);
+        self.globals.set("Fiber", Value::Object(fiber_ref));
+    }
+
+    pub fn get_global<T: FromValue>(&self, name: &str) -> Option<T> {
+        let value = self.globals.get(name)?;
+
+        T::from_value(*value, self)
+    }
+
+    pub fn set_global<T: IntoValue>(&mut self, name: &str, value: T) {
+        let value = value.into_value(self);
+
+        self.globals.set(name, value);
+    }
+
+    /// Sets where `print` statements write to, stdout by default
+    pub fn set_output(&mut self, out: Box<dyn Write>) {
+        self.out = out;
+    }
+
+    /// Sets where compile and runtime errors are reported, stderr by default
+    pub fn set_error_output(&mut self, err: Box<dyn Write>) {
+        self.err = err;
+    }
+
+    /// Allocates (or reuses) an interned string and returns its value
+    pub fn alloc_string(&mut self, s: &str) -> Value {
+        Value::Object(self.intern_string(s))
+    }
+
+    pub fn as_str(&self, value: Value) -> Option<&str> {
+        match value {
+            Value::Object(obj_ref) => match &self.heap[obj_ref.0] {
+                Object::String(str) => Some(&str.chars),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Items of the array `value` points to, natives use it to read and grow arrays
+    pub fn as_array(&mut self, value: Value) -> Option<&mut Vec<Value>> {
+        match value {
+            Value::Object(obj_ref) => match &mut self.heap[obj_ref.0] {
+                Object::Array(arr) => Some(&mut arr.items),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Formats `value` the way `print` shows it
+    pub fn format(&self, value: Value) -> String {
+        fmt_value(value, &self.heap)
+    }
+
+    pub fn run(&mut self) -> ExecResult {
+        // The script frame is gone once it returns or fails, only `load` pushes a new one
+        if self.frames.is_empty() {
+            let _ = writeln!(self.err, "Nothing loaded to run.");
+            return Err(ExecErr::RuntimeErr);
+        }
+
         loop {
             // region: Debugging output (--features dbg)
             #[cfg(feature = "dbg")]
             {
                 dbg_mem(&self.stack, &self.heap);
-                disasm_instr(self.ip, &self.chunk);
+                let frame = self.frames.last().expect("no function running");
+                disasm_instr(frame.ip, &frame.function.chunk);
             }
             // endregion: Debugging output (--features dbg)
 
@@ -107,10 +286,11 @@ impl<'a> VM<'a> {
                 OpCode::Dup => self.duuplicate(),
 
                 OpCode::Print => self.print(),
-                OpCode::DefGlob => self.def_global(),
-                OpCode::GetGlob => self.get_global(),
+                OpCode::ToStr => self.stringify(),
+                OpCode::DefGlob => self.def_glob(),
+                OpCode::GetGlob => self.get_glob(),
                 OpCode::GetLocal => self.get_local(),
-                OpCode::SetGlob => self.set_global(),
+                OpCode::SetGlob => self.set_glob(),
                 OpCode::SetLocal => self.set_local(),
 
                 OpCode::Jump => self.jump(),
@@ -132,8 +312,31 @@ impl<'a> VM<'a> {
                 OpCode::Mul => self.binary_op(ArithOp::Mul),
                 OpCode::Div => self.binary_op(ArithOp::Div),
                 OpCode::Mod => self.binary_op(ArithOp::Mod),
+                OpCode::IntDiv => self.binary_op(ArithOp::IntDiv),
+                OpCode::Pow => self.binary_op(ArithOp::Pow),
+
+                OpCode::BitAnd => self.binary_op(ArithOp::BitAnd),
+                OpCode::BitOr => self.binary_op(ArithOp::BitOr),
+                OpCode::BitXor => self.binary_op(ArithOp::BitXor),
+                OpCode::BitNot => self.bit_not(),
+                OpCode::Shl => self.binary_op(ArithOp::Shl),
+                OpCode::Shr => self.binary_op(ArithOp::Shr),
+
+                OpCode::BuildList => self.build_list(),
+                OpCode::GetIndex => self.get_index(),
+                OpCode::SetIndex => self.set_index(),
 
-                OpCode::Return => return Ok(()),
+                OpCode::GetProp => self.get_prop(),
+
+                OpCode::Call => self.call(),
+
+                OpCode::Return => {
+                    if self.return_()? {
+                        return Ok(());
+                    }
+
+                    Ok(())
+                }
                 // Should never happen
                 OpCode::_COUNT => return Err(ExecErr::CompileErr),
             }?
@@ -149,7 +352,7 @@ impl<'a> VM<'a> {
 
     fn jump(&mut self) -> ExecResult {
         let offset = self.read_short();
-        self.ip += offset as usize;
+        self.frame().ip += offset as usize;
 
         Ok(())
     }
@@ -158,7 +361,7 @@ impl<'a> VM<'a> {
         let offset = self.read_short();
 
         if self.last_stack()?.is_falsey() {
-            self.ip += offset as usize;
+            self.frame().ip += offset as usize;
         }
 
         Ok(())
@@ -166,18 +369,393 @@ impl<'a> VM<'a> {
 
     fn _loop(&mut self) -> ExecResult {
         let offset = self.read_short();
-        self.ip -= offset as usize;
+        self.frame().ip -= offset as usize;
+
+        Ok(())
+    }
+
+    fn build_list(&mut self) -> ExecResult {
+        let count = self.read_byte() as usize;
+
+        let Some(start) = self.stack.len().checked_sub(count) else {
+            self.runtime_err("Stack underflow");
+            return Err(ExecErr::RuntimeErr);
+        };
+
+        let items = self.stack.drain(start..).collect();
+        let arr_ref = self.allocate_obj(Object::Array(ArrObj { items }));
+        self.stack.push(Value::Object(arr_ref));
+
+        Ok(())
+    }
+
+    fn get_index(&mut self) -> ExecResult {
+        let index = self.pop_stack()?;
+        let array = self.pop_stack()?;
+
+        let (arr_ref, i) = self.array_slot(array, index)?;
+        let Object::Array(arr) = &self.heap[arr_ref.0] else {
+            unreachable!("array_slot only returns arrays");
+        };
+        self.stack.push(arr.items[i]);
+
+        Ok(())
+    }
+
+    /// Leaves the assigned value on the stack, assignment is an expression
+    fn set_index(&mut self) -> ExecResult {
+        let value = self.pop_stack()?;
+        let index = self.pop_stack()?;
+        let array = self.pop_stack()?;
+
+        let (arr_ref, i) = self.array_slot(array, index)?;
+        if let Object::Array(arr) = &mut self.heap[arr_ref.0] {
+            arr.items[i] = value;
+        }
+        self.stack.push(value);
+
+        Ok(())
+    }
+
+    /// Checks that `array[index]` exists, negative indices count from the end
+    fn array_slot(&mut self, array: Value, index: Value) -> Result<(ObjRef, usize), ExecErr> {
+        let len = match array {
+            Value::Object(arr_ref) => match &self.heap[arr_ref.0] {
+                Object::Array(arr) => Some((arr_ref, arr.items.len())),
+                _ => None,
+            },
+            _ => None,
+        };
+
+        let Some((arr_ref, len)) = len else {
+            self.runtime_err("Only arrays can be indexed.");
+            return Err(ExecErr::RuntimeErr);
+        };
+
+        let index = match index {
+            Value::Number(n) if n.fract() == 0.0 => n,
+            _ => {
+                self.runtime_err("Array index must be an integer.");
+                return Err(ExecErr::RuntimeErr);
+            }
+        };
+
+        let resolved = if index < 0.0 {
+            len as f64 + index
+        } else {
+            index
+        };
+        if resolved < 0.0 || resolved >= len as f64 {
+            self.runtime_err(&format!(
+                "Index {index} out of range for an array of length {len}."
+            ));
+            return Err(ExecErr::RuntimeErr);
+        }
+
+        Ok((arr_ref, resolved as usize))
+    }
+
+    fn get_prop(&mut self) -> ExecResult {
+        let name = self.read_str()?;
+        let target = self.pop_stack()?;
+
+        let Value::Object(obj_ref) = target else {
+            self.runtime_err("Only instances have properties.");
+            return Err(ExecErr::RuntimeErr);
+        };
+
+        let value = match (&self.heap[obj_ref.0], &*name) {
+            (Object::Namespace(ns), _) => ns.members.get(&*name).copied(),
+            (Object::Fiber(fiber), "isDone") => Some(Value::Boolean(fiber.is_done())),
+            (Object::Fiber(_), "call") => Some(Value::Object(self.fiber_call(obj_ref))),
+            (Object::Fiber(_), _) => None,
+            _ => {
+                self.runtime_err("Only instances have properties.");
+                return Err(ExecErr::RuntimeErr);
+            }
+        };
+
+        let Some(value) = value else {
+            self.runtime_err(&format!("Undefined property '{name}'."));
+            return Err(ExecErr::RuntimeErr);
+        };
+        self.stack.push(value);
+
+        Ok(())
+    }
+
+    /// Producer and consumer loops read `fiber.call` on every step, it's allocated once per fiber
+    fn fiber_call(&mut self, fiber_ref: ObjRef) -> ObjRef {
+        if let Object::Fiber(FiberObj {
+            call: Some(call), ..
+        }) = &self.heap[fiber_ref.0]
+        {
+            return *call;
+        }
+
+        let call = self.allocate_obj(Object::Builtin(Builtin::FiberCall(fiber_ref)));
+        if let Object::Fiber(fiber) = &mut self.heap[fiber_ref.0] {
+            fiber.call = Some(call);
+        }
+
+        call
+    }
+
+    fn call(&mut self) -> ExecResult {
+        let argc = self.read_byte() as usize;
+
+        let Some(callee_slot) = self.stack.len().checked_sub(argc + 1) else {
+            self.runtime_err("Stack underflow");
+            return Err(ExecErr::RuntimeErr);
+        };
+
+        let callee = self.stack[callee_slot];
+        let Value::Object(obj_ref) = callee else {
+            self.runtime_err("Can only call functions and classes.");
+            return Err(ExecErr::RuntimeErr);
+        };
+
+        match &self.heap[obj_ref.0] {
+            Object::Native(native) => {
+                let (arity, function) = (native.arity, native.function);
+                self.call_native(arity, function, argc, callee_slot)
+            }
+            Object::Function(function) => {
+                let function = Rc::clone(function);
+                self.call_function(function, argc, callee_slot)
+            }
+            Object::Builtin(builtin) => {
+                let builtin = *builtin;
+                self.call_builtin(builtin, argc, callee_slot)
+            }
+            _ => {
+                self.runtime_err("Can only call functions and classes.");
+                Err(ExecErr::RuntimeErr)
+            }
+        }
+    }
+
+    fn call_native(
+        &mut self,
+        arity: u8,
+        function: NativeFn,
+        argc: usize,
+        callee_slot: usize,
+    ) -> ExecResult {
+        if arity as usize != argc {
+            self.runtime_err(&format!("Expected {arity} arguments but got {argc}."));
+            return Err(ExecErr::RuntimeErr);
+        }
+
+        let args: Vec<Value> = self.stack.drain(callee_slot + 1..).collect();
+        let result = match function(self, &args) {
+            Ok(value) => value,
+            Err(msg) => {
+                self.runtime_err(&msg);
+                return Err(ExecErr::RuntimeErr);
+            }
+        };
+
+        self.stack.truncate(callee_slot);
+        self.stack.push(result);
 
         Ok(())
     }
 
-    fn set_global(&mut self) -> ExecResult {
-        let span = self.read_str()?;
-        let var_name = &self.src[span.start..span.end];
+    /// The callee and its arguments stay in the stack, they are the first slots of the new frame
+    fn call_function(
+        &mut self,
+        function: Rc<Function>,
+        argc: usize,
+        callee_slot: usize,
+    ) -> ExecResult {
+        if function.arity as usize != argc {
+            let arity = function.arity;
+            self.runtime_err(&format!("Expected {arity} arguments but got {argc}."));
+            return Err(ExecErr::RuntimeErr);
+        }
+
+        if self.frames.len() == Self::FRAMES_MAX {
+            self.runtime_err("Stack overflow.");
+            return Err(ExecErr::RuntimeErr);
+        }
+
+        self.frames.push(CallFrame {
+            function,
+            ip: 0,
+            slots: callee_slot,
+        });
+
+        Ok(())
+    }
+
+    fn call_builtin(&mut self, builtin: Builtin, argc: usize, callee_slot: usize) -> ExecResult {
+        let expected = match builtin {
+            Builtin::FiberNew => 1..=1,
+            Builtin::FiberYield | Builtin::FiberCall(_) => 0..=1,
+        };
+
+        if !expected.contains(&argc) {
+            let (min, max) = (expected.start(), expected.end());
+            let arity = if min == max {
+                min.to_string()
+            } else {
+                format!("{min} or {max}")
+            };
+            self.runtime_err(&format!("Expected {arity} arguments but got {argc}."));
+            return Err(ExecErr::RuntimeErr);
+        }
+
+        let arg = self
+            .stack
+            .get(callee_slot + 1)
+            .copied()
+            .unwrap_or(Value::Nil);
+        self.stack.truncate(callee_slot);
+
+        match builtin {
+            Builtin::FiberNew => self.new_fiber(arg),
+            Builtin::FiberCall(fiber) => self.resume_fiber(fiber, arg),
+            Builtin::FiberYield => self.yield_fiber(arg),
+        }
+    }
+
+    fn new_fiber(&mut self, function: Value) -> ExecResult {
+        let function = match function {
+            Value::Object(obj_ref) => match &self.heap[obj_ref.0] {
+                Object::Function(function) => Some(Rc::clone(function)),
+                _ => None,
+            },
+            _ => None,
+        };
+
+        let Some(function) = function.filter(|function| function.arity <= 1) else {
+            self.runtime_err("Fiber.new expects a function with 0 or 1 parameters.");
+            return Err(ExecErr::RuntimeErr);
+        };
+
+        let fiber = FiberObj {
+            stack: vec![function_value(self, &function)],
+            frames: vec![CallFrame {
+                function,
+                ip: 0,
+                slots: 0,
+            }],
+            caller: None,
+            fresh: true,
+            call: None,
+        };
+        let fiber_ref = self.allocate_obj(Object::Fiber(fiber));
+        self.stack.push(Value::Object(fiber_ref));
+
+        Ok(())
+    }
+
+    /**
+     * `fiber.call(value)` runs `fiber` until it yields or its function returns. The value is the
+     * argument of the fiber's function on the first call, and the result of the `Fiber.yield` the
+     * fiber stopped at on the next ones.
+     */
+    fn resume_fiber(&mut self, fiber_ref: ObjRef, value: Value) -> ExecResult {
+        let current = self.fiber;
+        let Object::Fiber(fiber) = &mut self.heap[fiber_ref.0] else {
+            unreachable!("call is only bound to fibers");
+        };
+
+        let error = if fiber.is_done() {
+            Some("Can't call a finished fiber.")
+        } else if fiber.caller.is_some() || fiber_ref == current {
+            Some("Fiber has already been called.")
+        } else {
+            None
+        };
+        if let Some(msg) = error {
+            self.runtime_err(msg);
+            return Err(ExecErr::RuntimeErr);
+        }
+
+        let takes_value = !fiber.fresh || fiber.frames[0].function.arity == 1;
+        fiber.fresh = false;
+        fiber.caller = Some(current);
+
+        self.switch_fiber(fiber_ref);
+        if takes_value {
+            self.stack.push(value);
+        }
+
+        Ok(())
+    }
+
+    /// Gives control back to the fiber that called the running one, `value` is what its `call` returns
+    fn yield_fiber(&mut self, value: Value) -> ExecResult {
+        let caller = match &mut self.heap[self.fiber.0] {
+            Object::Fiber(fiber) => fiber.caller.take(),
+            _ => None,
+        };
+
+        let Some(caller) = caller else {
+            self.runtime_err("Can't yield from the main fiber.");
+            return Err(ExecErr::RuntimeErr);
+        };
+
+        self.switch_fiber(caller);
+        self.stack.push(value);
+
+        Ok(())
+    }
+
+    /// Parks the stacks of the running fiber in its object and takes the ones of `to`
+    fn switch_fiber(&mut self, to: ObjRef) {
+        let stack = mem::take(&mut self.stack);
+        let frames = mem::take(&mut self.frames);
+        if let Object::Fiber(current) = &mut self.heap[self.fiber.0] {
+            current.stack = stack;
+            current.frames = frames;
+        }
+
+        if let Object::Fiber(next) = &mut self.heap[to.0] {
+            self.stack = mem::take(&mut next.stack);
+            self.frames = mem::take(&mut next.frames);
+        }
+        self.fiber = to;
+    }
+
+    /// Returns true when the script itself returned, that is the end of the program
+    fn return_(&mut self) -> Result<bool, ExecErr> {
+        let result = self.pop_stack()?;
+        let Some(frame) = self.frames.pop() else {
+            return Ok(true);
+        };
+
+        if !self.frames.is_empty() {
+            self.stack.truncate(frame.slots);
+            self.stack.push(result);
+
+            return Ok(false);
+        }
+
+        // The function of a fiber ended, the fiber that called it gets the returned value
+        let caller = match &mut self.heap[self.fiber.0] {
+            Object::Fiber(fiber) => fiber.caller.take(),
+            _ => None,
+        };
+        let Some(caller) = caller else {
+            return Ok(true);
+        };
+
+        self.stack.clear();
+        self.switch_fiber(caller);
+        self.stack.push(result);
+
+        Ok(false)
+    }
+
+    fn set_glob(&mut self) -> ExecResult {
+        let var_name = self.read_str()?;
 
         let value = self.last_stack()?;
-        if self.globals.set(var_name, value) {
-            self.globals.delete(var_name);
+        if self.globals.set(&var_name, value) {
+            self.globals.delete(&var_name);
             self.runtime_err(&format!("Undefined variable '{var_name}' "));
             return Err(ExecErr::RuntimeErr);
         }
@@ -186,17 +764,16 @@ impl<'a> VM<'a> {
     }
 
     fn set_local(&mut self) -> ExecResult {
-        let slot = self.read_byte() as usize;
+        let slot = self.read_byte() as usize + self.frame().slots;
         self.stack[slot] = self.last_stack()?;
 
         Ok(())
     }
 
-    fn get_global(&mut self) -> ExecResult {
-        let span = self.read_str()?;
-        let var_name = &self.src[span.start..span.end];
+    fn get_glob(&mut self) -> ExecResult {
+        let var_name = self.read_str()?;
 
-        let Some(value) = self.globals.get(var_name) else {
+        let Some(value) = self.globals.get(&var_name) else {
             self.runtime_err(&format!("Undefine variable '{var_name}'"));
             return Err(ExecErr::RuntimeErr);
         };
@@ -206,7 +783,7 @@ impl<'a> VM<'a> {
     }
 
     fn get_local(&mut self) -> ExecResult {
-        let slot = self.read_byte() as usize;
+        let slot = self.read_byte() as usize + self.frame().slots;
         let value = self.stack[slot];
 
         self.stack.push(value);
@@ -214,26 +791,36 @@ impl<'a> VM<'a> {
         Ok(())
     }
 
-    fn def_global(&mut self) -> ExecResult {
-        let Span { start, end } = self.read_str()?;
+    fn def_glob(&mut self) -> ExecResult {
+        let var_name = self.read_str()?;
 
         let value = self.pop_stack()?;
-        self.globals.set(&self.src[start..end], value);
+        self.globals.set(&var_name, value);
 
         Ok(())
     }
 
     fn print(&mut self) -> ExecResult {
         let value = self.pop_stack()?;
-        if let Value::Object(id) = value {
-            println!("{}", self.heap[id.0]);
-        } else {
-            println!("{}", value);
+        let res = writeln!(self.out, "{}", fmt_value(value, &self.heap));
+
+        if let Err(err) = res {
+            self.runtime_err(&format!("I/O error: {err}"));
+            return Err(ExecErr::RuntimeErr);
         }
 
         Ok(())
     }
 
+    fn stringify(&mut self) -> ExecResult {
+        let value = self.pop_stack()?;
+        let text = fmt_value(value, &self.heap);
+        let obj = self.intern_string(&text);
+        self.stack.push(Value::Object(obj));
+
+        Ok(())
+    }
+
     fn constant(&mut self) -> ExecResult {
         let value = self.make_value()?;
         self.stack.push(value);
@@ -278,6 +865,8 @@ impl<'a> VM<'a> {
             (Object::String(a_str), Object::String(b_str), CompareOp::Greater) => {
                 a_str.lenght > b_str.lenght
             }
+            (_, _, CompareOp::Equal) => a_ref == b_ref,
+            _ => false,
         }
     }
 
@@ -311,6 +900,21 @@ impl<'a> VM<'a> {
         Ok(())
     }
 
+    fn bit_not(&mut self) -> ExecResult {
+        let Value::Number(value) = self.operand()? else {
+            self.runtime_err("Operand must be a number.");
+            return Err(ExecErr::RuntimeErr);
+        };
+
+        let Ok(int) = integer(*value) else {
+            self.runtime_err("Operands must be integers.");
+            return Err(ExecErr::RuntimeErr);
+        };
+        *value = !int as f64;
+
+        Ok(())
+    }
+
     fn binary_op(&mut self, op: ArithOp) -> ExecResult {
         let (a, b) = self.operands()?;
 
@@ -319,9 +923,18 @@ impl<'a> VM<'a> {
             _ => a.arithmetic(b, op),
         };
 
-        let Ok(value) = res else {
-            self.runtime_err("Invalid operands. ");
-            return Err(ExecErr::RuntimeErr);
+        let value = match res {
+            Ok(value) => value,
+            Err(err) => {
+                let msg = match err {
+                    ArithmeticError::DivisionByZero => "Division by zero.",
+                    ArithmeticError::InvalidOperands => "Invalid operands. ",
+                    ArithmeticError::NonIntegral => "Operands must be integers.",
+                    ArithmeticError::InvalidShift => "Shift amount must be between 0 and 63.",
+                };
+                self.runtime_err(msg);
+                return Err(ExecErr::RuntimeErr);
+            }
         };
 
         self.stack.push(value);
@@ -346,16 +959,17 @@ impl<'a> VM<'a> {
     }
 
     fn make_value(&mut self) -> Result<Value, ExecErr> {
-        let constant = self.read_const();
+        let constant = self.read_const().clone();
 
-        let value = match *constant {
+        let value = match constant {
             Constant::Number(num) => Value::Number(num),
             Constant::Boolean(b) => Value::Boolean(b),
             Constant::Nil => Value::Nil,
-            Constant::String { start, end } => {
-                let str_ref = self.intern_string(&self.src[start..end]);
+            Constant::String(chars) => {
+                let str_ref = self.intern_string(&chars);
                 Value::Object(str_ref)
             }
+            Constant::Function(function) => function_value(self, &function),
         };
 
         Ok(value)
@@ -380,10 +994,23 @@ impl<'a> VM<'a> {
         str_ref
     }
 
+    /// Reports `msg` with the line of each function being run, the innermost one first
     fn runtime_err(&mut self, msg: &str) {
-        let line = self.chunk.rles.get_ln(self.ip - 1);
-        eprintln!("{msg} [line {line}] in script");
+        for (i, frame) in self.frames.iter().rev().enumerate() {
+            let line = frame.function.chunk.rles.get_ln(frame.ip.saturating_sub(1));
+            let location = match &*frame.function.name {
+                "" => "script".to_string(),
+                name => format!("{name}()"),
+            };
+
+            let _ = match i {
+                0 => writeln!(self.err, "{msg} [line {line}] in {location}"),
+                _ => writeln!(self.err, "[line {line}] in {location}"),
+            };
+        }
+
         self.stack.clear();
+        self.frames.clear();
     }
 
     fn last_stack(&mut self) -> Result<Value, ExecErr> {
@@ -405,17 +1032,19 @@ impl<'a> VM<'a> {
     }
 
     fn read_short(&mut self) -> u16 {
-        let bytes = [self.chunk.code[self.ip], self.chunk.code[self.ip + 1]];
-        self.ip += 2;
+        let frame = self.frame();
+        let code = &frame.function.chunk.code;
+        let bytes = [code[frame.ip], code[frame.ip + 1]];
+        frame.ip += 2;
 
         u16::from_be_bytes(bytes)
     }
 
-    fn read_str(&mut self) -> Result<Span, ExecErr> {
+    fn read_str(&mut self) -> Result<Rc<str>, ExecErr> {
         let const_ = self.read_const();
 
-        if let Constant::String { start, end } = *const_ {
-            Ok(Span { start, end })
+        if let Constant::String(chars) = const_ {
+            Ok(chars.clone())
         } else {
             // Invalid string constant
             Err(ExecErr::CompileErr)
@@ -425,14 +1054,374 @@ impl<'a> VM<'a> {
     fn read_const(&mut self) -> &Constant {
         let const_ = self.read_byte();
 
-        &self.chunk.constants[const_ as usize]
+        &self.frame().function.chunk.constants[const_ as usize]
     }
 
     #[inline]
     fn read_byte(&mut self) -> u8 {
-        let byte = self.chunk.code[self.ip];
-        self.ip += 1;
+        let frame = self.frame();
+        let byte = frame.function.chunk.code[frame.ip];
+        frame.ip += 1;
 
         byte
     }
+
+    #[inline]
+    fn frame(&mut self) -> &mut CallFrame {
+        self.frames.last_mut().expect("no function running")
+    }
+}
+
+/// Function objects are allocated each time their declaration runs, the code is shared
+fn function_value(vm: &mut VM, function: &Rc<Function>) -> Value {
+    Value::Object(vm.allocate_obj(Object::Function(Rc::clone(function))))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, io, rc::Rc};
+
+    use super::*;
+
+    /// In-memory sink, so tests can read what `print` and the error reports wrote
+    #[derive(Clone, Default)]
+    struct Capture(Rc<RefCell<Vec<u8>>>);
+
+    impl Capture {
+        fn contents(&self) -> String {
+            String::from_utf8_lossy(&self.0.borrow()).to_string()
+        }
+    }
+
+    impl Write for Capture {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn captured_vm() -> (VM, Capture, Capture) {
+        let (out, err) = (Capture::default(), Capture::default());
+        let mut vm = VM::new();
+        vm.set_output(Box::new(out.clone()));
+        vm.set_error_output(Box::new(err.clone()));
+
+        (vm, out, err)
+    }
+
+    /// Runs `src` in a new VM and returns the result with what was printed and reported
+    fn run(src: &str) -> (ExecResult, String, String) {
+        let (mut vm, out, err) = captured_vm();
+        let res = vm.load(src).and_then(|_| vm.run());
+
+        (res, out.contents(), err.contents())
+    }
+
+    fn double(_: &mut VM, args: &[Value]) -> Result<Value, String> {
+        match args[0] {
+            Value::Number(n) => Ok(Value::Number(n * 2.0)),
+            _ => Err("Expected a number.".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_globals_survive_between_loads() {
+        let (mut vm, out, _) = captured_vm();
+
+        vm.load("var a = 1;").unwrap();
+        vm.run().unwrap();
+        vm.load("a = a + 1; print a;").unwrap();
+        vm.run().unwrap();
+
+        assert_eq!(out.contents(), "2\n");
+        assert_eq!(vm.get_global::<f64>("a"), Some(2.0));
+    }
+
+    #[test]
+    fn test_run_without_a_loaded_script() {
+        let (mut vm, _, err) = captured_vm();
+        assert!(matches!(vm.run(), Err(ExecErr::RuntimeErr)));
+        assert_eq!(err.contents(), "Nothing loaded to run.\n");
+    }
+
+    #[test]
+    fn test_run_twice_after_success() {
+        let (mut vm, out, err) = captured_vm();
+
+        vm.load("print 1;").unwrap();
+        vm.run().unwrap();
+        assert!(matches!(vm.run(), Err(ExecErr::RuntimeErr)));
+
+        assert_eq!(out.contents(), "1\n");
+        assert_eq!(err.contents(), "Nothing loaded to run.\n");
+    }
+
+    #[test]
+    fn test_run_twice_after_runtime_error() {
+        let (mut vm, _, err) = captured_vm();
+
+        vm.load("print nope;").unwrap();
+        assert!(matches!(vm.run(), Err(ExecErr::RuntimeErr)));
+        assert!(matches!(vm.run(), Err(ExecErr::RuntimeErr)));
+
+        assert!(err
+            .contents()
+            .ends_with("in script\nNothing loaded to run.\n"));
+    }
+
+    #[test]
+    fn test_host_globals() {
+        let (mut vm, out, _) = captured_vm();
+        vm.set_global("name", "lox");
+        vm.set_global("ready", true);
+
+        vm.load("var greeting = \"hi \" + name; print ready;")
+            .unwrap();
+        vm.run().unwrap();
+
+        assert_eq!(out.contents(), "true\n");
+        assert_eq!(
+            vm.get_global::<String>("greeting"),
+            Some("hi lox".to_string())
+        );
+        assert_eq!(vm.get_global::<f64>("greeting"), None);
+        assert_eq!(vm.get_global::<f64>("missing"), None);
+    }
+
+    #[test]
+    fn test_host_natives() {
+        let (mut vm, out, err) = captured_vm();
+        vm.define_native("double", 1, double);
+
+        vm.load("print double(21);").unwrap();
+        vm.run().unwrap();
+        assert_eq!(out.contents(), "42\n");
+
+        vm.load("double(\"a\");").unwrap();
+        assert_eq!(vm.run(), Err(ExecErr::RuntimeErr));
+        assert_eq!(err.contents(), "Expected a number. [line 1] in script\n");
+    }
+
+    #[test]
+    fn test_errors_go_to_the_error_sink() {
+        let (res, out, err) = run("print 1; print nope;");
+        assert_eq!(res, Err(ExecErr::RuntimeErr));
+        assert_eq!(out, "1\n");
+        assert_eq!(err, "Undefine variable 'nope' [line 1] in script\n");
+
+        let (res, _, err) = run("print ;");
+        assert_eq!(res, Err(ExecErr::CompileErr));
+        assert!(err.contains("Expect expression."), "{err}");
+    }
+
+    #[test]
+    fn test_arrays() {
+        let (res, out, _) = run("
+            var a = [1, \"two\", [3]];
+            print a;
+            print a[-1][0];
+            a[1] = nil;
+            append(a, 4);
+            print len(a);
+            print a;
+            print len(\"héllo\");
+        ");
+
+        assert_eq!(res, Ok(()));
+        assert_eq!(out, "[1, \"two\", [3]]\n3\n4\n[1, nil, [3], 4]\n5\n");
+    }
+
+    #[test]
+    fn test_string_interpolation() {
+        let (res, out, _) = run(r#"
+            var n = 5;
+            print "The factorial of ${n} is ${n * 24}";
+            print "${"in ${n + 1}"} ${[1, "a"]} ${nil}${true} \${n}";
+        "#);
+
+        assert_eq!(res, Ok(()));
+        assert_eq!(
+            out,
+            "The factorial of 5 is 120\nin 6 [1, \"a\"] niltrue ${n}\n"
+        );
+    }
+
+    #[test]
+    fn test_array_index_errors() {
+        let cases = [
+            (
+                "[1, 2][2];",
+                "Index 2 out of range for an array of length 2.",
+            ),
+            (
+                "[1, 2][-3] = 0;",
+                "Index -3 out of range for an array of length 2.",
+            ),
+            ("[1][0.5];", "Array index must be an integer."),
+            ("var s = \"ab\"; s[0];", "Only arrays can be indexed."),
+            ("append(1, 2);", "Can only append to arrays."),
+        ];
+
+        for (src, msg) in cases {
+            let (res, _, err) = run(src);
+            assert_eq!(res, Err(ExecErr::RuntimeErr), "{src}");
+            assert_eq!(err, format!("{msg} [line 1] in script\n"), "{src}");
+        }
+    }
+
+    #[test]
+    fn test_integer_operators() {
+        let (res, out, _) = run("
+            print 7 ~/ 2;
+            print -7 ~/ 2;
+            print 2 ** 10;
+            print [6 & 3, 6 | 3, 6 ^ 3, ~5];
+            print [1 << 4, -16 >> 2];
+            print 0xff + 0b1 + 1_000;
+        ");
+
+        assert_eq!(res, Ok(()));
+        assert_eq!(out, "3\n-4\n1024\n[2, 7, 5, -6]\n[16, -4]\n1256\n");
+    }
+
+    #[test]
+    fn test_integer_operator_errors() {
+        let cases = [
+            ("1 ~/ 0;", "Division by zero."),
+            ("1.5 & 1;", "Operands must be integers."),
+            ("~0.5;", "Operands must be integers."),
+            ("1 << 64;", "Shift amount must be between 0 and 63."),
+        ];
+
+        for (src, msg) in cases {
+            let (res, _, err) = run(src);
+            assert_eq!(res, Err(ExecErr::RuntimeErr), "{src}");
+            assert_eq!(err, format!("{msg} [line 1] in script\n"), "{src}");
+        }
+    }
+
+    #[test]
+    fn test_functions_and_recursion() {
+        let (res, out, _) = run("
+            fun fib(n) {
+                if (n < 2) return n;
+                return fib(n - 1) + fib(n - 2);
+            }
+            fun noReturn() {}
+            print fib(15);
+            print noReturn();
+            print fib;
+        ");
+
+        assert_eq!(res, Ok(()));
+        assert_eq!(out, "610\nnil\n<fn fib>\n");
+    }
+
+    #[test]
+    fn test_call_errors() {
+        let (res, _, err) = run("fun f(a) {}\nf();");
+        assert_eq!(res, Err(ExecErr::RuntimeErr));
+        assert_eq!(err, "Expected 1 arguments but got 0. [line 2] in script\n");
+
+        let (res, _, err) = run("var x = 1;\nx();");
+        assert_eq!(res, Err(ExecErr::RuntimeErr));
+        assert_eq!(
+            err,
+            "Can only call functions and classes. [line 2] in script\n"
+        );
+    }
+
+    #[test]
+    fn test_stack_overflow_and_trace() {
+        let (res, _, err) = run("fun f() { return f(); }\nf();");
+        assert_eq!(res, Err(ExecErr::RuntimeErr));
+
+        let lines: Vec<&str> = err.lines().collect();
+        assert_eq!(lines.len(), VM::FRAMES_MAX);
+        assert_eq!(lines[0], "Stack overflow. [line 1] in f()");
+        assert_eq!(lines[VM::FRAMES_MAX - 2], "[line 1] in f()");
+        assert_eq!(lines[VM::FRAMES_MAX - 1], "[line 2] in script");
+    }
+
+    #[test]
+    fn test_fiber_values() {
+        let (res, out, _) = run("
+            fun gen(first) {
+                var x = Fiber.yield(first + 1);
+                var y = Fiber.yield(x * 2);
+                return y + 100;
+            }
+            var f = Fiber.new(gen);
+            print f.call(1);
+            print f.isDone;
+            print f.call(5);
+            print f.call(7);
+            print f.isDone;
+
+            fun noParams() { Fiber.yield(\"a\"); }
+            var g = Fiber.new(noParams);
+            print g.call(\"ignored\");
+            print g.call();
+            print g.isDone;
+        ");
+
+        assert_eq!(res, Ok(()));
+        assert_eq!(out, "2\nfalse\n10\n107\ntrue\na\nnil\ntrue\n");
+    }
+
+    #[test]
+    fn test_fiber_errors() {
+        let cases = [
+            (
+                "fun f() {} var f1 = Fiber.new(f); f1.call(); f1.call();",
+                "Can't call a finished fiber. [line 1] in script\n",
+            ),
+            (
+                "Fiber.yield(1);",
+                "Can't yield from the main fiber. [line 1] in script\n",
+            ),
+            (
+                "fun two(a, b) {} Fiber.new(two);",
+                "Fiber.new expects a function with 0 or 1 parameters. [line 1] in script\n",
+            ),
+            (
+                "fun bad() {\nFiber.yield(1);\nreturn nope;\n}\nvar b = Fiber.new(bad);\nb.call();\nb.call();",
+                "Undefine variable 'nope' [line 3] in bad()\n",
+            ),
+        ];
+
+        for (src, msg) in cases {
+            let (res, _, err) = run(src);
+            assert_eq!(res, Err(ExecErr::RuntimeErr), "{src}");
+            assert_eq!(err, msg, "{src}");
+        }
+    }
+
+    #[test]
+    fn test_fiber_loop_doesnt_grow_the_heap() {
+        let heap_after = |times: usize| {
+            let (mut vm, _, _) = captured_vm();
+            let src = format!(
+                "
+                fun producer() {{
+                    var i = 0;
+                    while (true) {{ Fiber.yield(i); i = i + 1; }}
+                }}
+                var p = Fiber.new(producer);
+                var i = 0;
+                while (i < {times}) {{ p.call(); i = i + 1; }}
+                "
+            );
+            vm.load(&src).unwrap();
+            vm.run().unwrap();
+
+            vm.heap.len()
+        };
+
+        assert_eq!(heap_after(100), heap_after(10));
+    }
 }
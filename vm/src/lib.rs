@@ -1,5 +1,6 @@
 pub mod chunk;
 pub mod compiler;
+pub mod convert;
 pub mod dbg;
 pub mod exec;
 pub mod scanner;
@@ -19,6 +19,8 @@ fn main() {
 
 fn repl() {
     let mut line = String::new();
+    // Shared between lines, so globals defined in one line are visible in the next
+    let mut vm = VM::new();
 
     loop {
         print!("> ");
@@ -35,7 +37,7 @@ fn repl() {
             break;
         }
 
-        if let Err(err) = VM::interpret(&line) {
+        if let Err(err) = vm.load(&line).and_then(|_| vm.run()) {
             eprintln!("{err:?}");
         }
 
@@ -3,6 +3,9 @@ pub struct Scanner<'a> {
     pub start: usize,
     pub curr: usize,
     pub line: usize,
+
+    /// Open `${` in strings, each one with the count of `{` opened inside it
+    interpolations: Vec<usize>,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -36,6 +39,8 @@ pub enum TokenKind {
     RightParen,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
 
     Comma,
     Dot,
@@ -46,6 +51,10 @@ pub enum TokenKind {
     Slash,
     Star,
     Percent,
+    Ampersand,
+    Pipe,
+    Caret,
+    Tilde,
 
     // One or two character tokens.
     Bang,
@@ -57,6 +66,11 @@ pub enum TokenKind {
     Less,
     LessEqual,
 
+    LessLess,
+    GreaterGreater,
+    TildeSlash,
+    StarStar,
+
     PlusEqual,
     MinusEqual,
     StarEqual,
@@ -65,6 +79,8 @@ pub enum TokenKind {
     // Literals.
     Identifier,
     String,
+    /// Piece of a string that ends with `${`, the tokens of the expression follow it
+    Interpolation,
     Number,
 
     // Keywords.
@@ -99,6 +115,7 @@ impl<'a> Scanner<'a> {
             start: 0,
             curr: 0,
             line: 1,
+            interpolations: Vec::new(),
         }
     }
 
@@ -107,28 +124,68 @@ impl<'a> Scanner<'a> {
 
         self.start = self.curr;
 
-        let Some(c) = self.advance() else {
+        let Some(&c) = self.advance() else {
+            // `"a ${b` ends before the `}` that closes the interpolation
+            if !self.interpolations.is_empty() {
+                self.interpolations.clear();
+                return Err(self.make_err("Unterminated string."));
+            }
+
             return Ok(self.make_token(TokenKind::EOF));
         };
 
-        if (*c as char).is_numeric() {
-            return Ok(self.number());
+        if c.is_ascii_digit() {
+            return self.number();
         }
 
-        if (*c as char).is_alphabetic() || *c == b'_' {
+        if c == b'r' && self.peek() == Some(&b'"') {
+            self.advance(); // consume "
+            return self.raw_string();
+        }
+
+        if (c as char).is_alphabetic() || c == b'_' {
             return Ok(self.identifier());
         }
 
-        let t = match *c as char {
+        let t = match c as char {
             '(' => self.make_token(TokenKind::LeftParen),
             ')' => self.make_token(TokenKind::RightParen),
-            '{' => self.make_token(TokenKind::LeftBrace),
-            '}' => self.make_token(TokenKind::RightBrace),
+            '{' => {
+                if let Some(depth) = self.interpolations.last_mut() {
+                    *depth += 1;
+                }
+
+                self.make_token(TokenKind::LeftBrace)
+            }
+            // The `}` that closes an interpolation resumes the string it started in
+            '}' if self.interpolations.last() == Some(&0) => {
+                self.interpolations.pop();
+                return self.string();
+            }
+            '}' => {
+                if let Some(depth) = self.interpolations.last_mut() {
+                    *depth -= 1;
+                }
+
+                self.make_token(TokenKind::RightBrace)
+            }
+            '[' => self.make_token(TokenKind::LeftBracket),
+            ']' => self.make_token(TokenKind::RightBracket),
             ';' => self.make_token(TokenKind::Semicolon),
             ':' => self.make_token(TokenKind::Colon),
             ',' => self.make_token(TokenKind::Comma),
             '.' => self.make_token(TokenKind::Dot),
             '%' => self.make_token(TokenKind::Percent),
+            '&' => self.make_token(TokenKind::Ampersand),
+            '|' => self.make_token(TokenKind::Pipe),
+            '^' => self.make_token(TokenKind::Caret),
+            '~' => {
+                if self.match_('/') {
+                    self.make_token(TokenKind::TildeSlash)
+                } else {
+                    self.make_token(TokenKind::Tilde)
+                }
+            }
             '-' => {
                 if self.match_('=') {
                     self.make_token(TokenKind::MinusEqual)
@@ -151,7 +208,9 @@ impl<'a> Scanner<'a> {
                 }
             }
             '*' => {
-                if self.match_('=') {
+                if self.match_('*') {
+                    self.make_token(TokenKind::StarStar)
+                } else if self.match_('=') {
                     self.make_token(TokenKind::StarEqual)
                 } else {
                     self.make_token(TokenKind::Star)
@@ -174,6 +233,8 @@ impl<'a> Scanner<'a> {
             '<' => {
                 if self.match_('=') {
                     self.make_token(TokenKind::LessEqual)
+                } else if self.match_('<') {
+                    self.make_token(TokenKind::LessLess)
                 } else {
                     self.make_token(TokenKind::Less)
                 }
@@ -181,6 +242,8 @@ impl<'a> Scanner<'a> {
             '>' => {
                 if self.match_('=') {
                     self.make_token(TokenKind::GreaterEqual)
+                } else if self.match_('>') {
+                    self.make_token(TokenKind::GreaterGreater)
                 } else {
                     self.make_token(TokenKind::Greater)
                 }
@@ -192,32 +255,138 @@ impl<'a> Scanner<'a> {
         Ok(t)
     }
 
-    fn number(&mut self) -> Token {
-        while byte_to_char_or(self.peek(), ' ').is_numeric() {
+    /// Decimals with an optional fraction and exponent, `0x` hex and `0b` binary, all with `_` separators
+    fn number(&mut self) -> Result<Token, ScannerError> {
+        let radix = match (self.src[self.start], self.peek()) {
+            (b'0', Some(b'x' | b'X')) => 16,
+            (b'0', Some(b'b' | b'B')) => 2,
+            _ => 10,
+        };
+
+        if radix != 10 {
+            self.advance(); // consume the prefix
+            self.digits(radix);
+        } else {
+            self.digits(10);
+
+            if let (Some(b'.'), Some(c)) = (self.peek(), self.peek_next()) {
+                if c.is_ascii_digit() {
+                    self.advance(); // consume .
+                    self.digits(10);
+                }
+            }
+
+            let exponent_digit = match self.peek_next() {
+                Some(b'+' | b'-') => self.src.get(self.curr + 2),
+                other => other,
+            };
+            if let (Some(b'e' | b'E'), Some(c)) = (self.peek(), exponent_digit) {
+                if c.is_ascii_digit() {
+                    self.advance(); // consume e
+                    if let Some(b'+' | b'-') = self.peek() {
+                        self.advance();
+                    }
+                    self.digits(10);
+                }
+            }
+        }
+
+        // `0b102` or `12abc` are one bad literal, not a number followed by something else
+        let mut invalid = false;
+        while byte_to_char_or(self.peek(), ' ').is_alphanumeric() || self.peek() == Some(&b'_') {
+            invalid = true;
             self.advance();
         }
 
-        if let Some(b'.') = self.peek() {
-            while byte_to_char_or(self.peek(), ' ').is_numeric() {
-                self.advance();
-            }
+        let lexeme = self.lexeme_str(self.start, self.curr);
+        if invalid || parse_number(lexeme).is_none() {
+            return Err(self.make_err("Invalid number literal."));
         }
 
-        self.make_token(TokenKind::Number)
+        Ok(self.make_token(TokenKind::Number))
     }
 
+    fn digits(&mut self, radix: u32) {
+        while byte_to_char_or(self.peek(), ' ').is_digit(radix) || self.peek() == Some(&b'_') {
+            self.advance();
+        }
+    }
+
+    /**
+     * Escapes are only skipped here, they are checked with `unescape` and decoded by the compiler.
+     *
+     * `"a ${b} c"` is scanned as `Interpolation` (`"a ${`), the tokens of `b`, then `String` (`} c"`).
+     * The compiler puts the pieces back together (see `Compiler::interpolation`).
+     */
     fn string(&mut self) -> Result<Token, ScannerError> {
+        let line = self.line;
+
+        let kind = loop {
+            match self.peek() {
+                None => return Err(self.make_err_at(line, "Unterminated string.")),
+                Some(b'"') => {
+                    self.advance();
+                    break TokenKind::String;
+                }
+                Some(b'$') if self.peek_next() == Some(&b'{') => {
+                    self.advance();
+                    self.advance();
+                    self.interpolations.push(0);
+                    break TokenKind::Interpolation;
+                }
+                Some(b'\\') => {
+                    self.advance();
+                    if let Some(b'\n') = self.advance() {
+                        self.line += 1;
+                    }
+                }
+                Some(b'\n') => {
+                    self.line += 1;
+                    self.advance();
+                }
+                Some(_) => {
+                    self.advance();
+                }
+            }
+        };
+
+        let mut token = self.make_token(kind);
+        let body = token.string_body(self.lexeme_str(self.start, self.curr));
+        if let Err((offset, msg)) = unescape(body) {
+            let line = line + body[..offset].matches('\n').count();
+            return Err(self.make_err_at(line, &msg));
+        }
+
+        token.line = line;
+
+        Ok(token)
+    }
+
+    /// `r"..."` keeps backslashes as they are, it ends at the first `"`
+    fn raw_string(&mut self) -> Result<Token, ScannerError> {
+        let line = self.line;
+
         while byte_to_char_or(self.peek(), '"') != '"' {
-            self.advance();
+            if let Some(b'\n') = self.advance() {
+                self.line += 1;
+            }
         }
 
-        if let None = self.peek() {
-            return Err(self.make_err("Unterminated string."));
+        if self.peek().is_none() {
+            return Err(self.make_err_at(line, "Unterminated string."));
         }
 
         self.advance(); // consume "
 
-        Ok(self.make_token(TokenKind::String))
+        let mut token = self.make_token(TokenKind::String);
+        token.line = line;
+
+        Ok(token)
+    }
+
+    fn lexeme_str(&self, start: usize, end: usize) -> &'a str {
+        // The bytes come from a `&str` and the bounds are next to ASCII quotes
+        std::str::from_utf8(&self.src[start..end]).unwrap_or_default()
     }
 
     fn identifier(&mut self) -> Token {
@@ -353,12 +522,14 @@ impl<'a> Scanner<'a> {
             match *c as char {
                 ' ' | '\t' | '\r' => self.advance(),
                 '/' => {
-                    if let Some(b'/') = self.peek_next() {
-                        while *self.peek().unwrap_or(&b'\n') != b'\n' {
-                            self.advance();
-                        }
+                    if self.peek_next() != Some(&b'/') {
+                        break;
+                    }
+
+                    while *self.peek().unwrap_or(&b'\n') != b'\n' {
+                        self.advance();
                     }
-                    break;
+                    continue;
                 }
                 '\n' => {
                     self.line += 1;
@@ -370,9 +541,13 @@ impl<'a> Scanner<'a> {
     }
 
     fn make_err(&self, desc: &str) -> ScannerError {
+        self.make_err_at(self.line, desc)
+    }
+
+    fn make_err_at(&self, line: usize, desc: &str) -> ScannerError {
         ScannerError {
             desc: desc.to_string(),
-            line: self.line,
+            line,
             start: self.start,
             end: self.curr,
         }
@@ -387,6 +562,113 @@ impl Token {
 
         &src[self.span.start..self.span.end]
     }
+
+    /// Contents of a string token without the quotes, with its escapes decoded
+    pub fn string_value(&self, src: &str) -> String {
+        let lexeme = self.lexeme(src);
+
+        match lexeme.strip_prefix('r') {
+            Some(raw) => raw[1..raw.len() - 1].to_string(),
+            None => unescape(self.string_body(lexeme)).unwrap_or_default(),
+        }
+    }
+
+    /// A piece of a string starts with `"` or the `}` of an interpolation, and ends with `"` or `${`
+    fn string_body<'a>(&self, lexeme: &'a str) -> &'a str {
+        let end = match self.kind {
+            TokenKind::Interpolation => lexeme.len() - 2,
+            _ => lexeme.len() - 1,
+        };
+
+        &lexeme[1..end]
+    }
+}
+
+/// Value of a number literal, `None` when a `_` isn't between two digits or there are no digits
+pub fn parse_number(lexeme: &str) -> Option<f64> {
+    let (radix, body) = match lexeme.get(..2) {
+        Some("0x" | "0X") => (16, &lexeme[2..]),
+        Some("0b" | "0B") => (2, &lexeme[2..]),
+        _ => (10, lexeme),
+    };
+
+    let bytes = body.as_bytes();
+    let separators_ok = bytes.iter().enumerate().all(|(i, b)| {
+        *b != b'_'
+            || (i > 0
+                && i + 1 < bytes.len()
+                && (bytes[i - 1] as char).is_digit(radix)
+                && (bytes[i + 1] as char).is_digit(radix))
+    });
+    if !separators_ok {
+        return None;
+    }
+
+    let digits = body.replace('_', "");
+    if radix == 10 {
+        digits.parse().ok()
+    } else {
+        u64::from_str_radix(&digits, radix).ok().map(|n| n as f64)
+    }
+}
+
+/**
+ * Decodes the escapes of a string body: `\n`, `\t`, `\r`, `\0`, `\\`, `\"`, `\'` and `\u{...}` with
+ * 1 to 6 hex digits. On error returns the byte offset of the bad escape, so the scanner can tell its line.
+ */
+pub fn unescape(body: &str) -> Result<String, (usize, String)> {
+    let mut out = String::with_capacity(body.len());
+    let mut chars = body.char_indices();
+
+    while let Some((_, c)) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+
+        let Some((offset, escape)) = chars.next() else {
+            return Err((body.len(), "Unterminated escape sequence.".to_string()));
+        };
+
+        let decoded = match escape {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            '0' => '\0',
+            '\\' => '\\',
+            '"' => '"',
+            '\'' => '\'',
+            '$' => '$',
+            'u' => {
+                let rest = &body[offset + 1..];
+                let code = rest
+                    .strip_prefix('{')
+                    .and_then(|rest| rest.split_once('}'))
+                    .map(|(digits, _)| digits)
+                    .filter(|digits| (1..=6).contains(&digits.len()))
+                    .filter(|digits| digits.chars().all(|c| c.is_ascii_hexdigit()))
+                    .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+                    .and_then(char::from_u32);
+
+                let Some(code) = code else {
+                    return Err((offset, "Invalid unicode escape sequence.".to_string()));
+                };
+
+                // Skip `{`, the hex digits and `}`
+                let len = rest.find('}').unwrap_or(0) + 1;
+                for _ in 0..len {
+                    chars.next();
+                }
+
+                code
+            }
+            other => return Err((offset, format!("Invalid escape sequence '\\{other}'."))),
+        };
+
+        out.push(decoded);
+    }
+
+    Ok(out)
 }
 
 #[inline]
@@ -416,3 +698,122 @@ impl core::fmt::Display for ScannerError {
 }
 
 impl core::error::Error for ScannerError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tokens of `src` up to (not including) EOF, or the first scan error
+    fn scan(src: &str) -> Result<Vec<Token>, ScannerError> {
+        let mut scanner = Scanner::new(src);
+        let mut tokens = Vec::new();
+
+        loop {
+            let token = scanner.scan_token()?;
+            if token.kind == TokenKind::EOF {
+                return Ok(tokens);
+            }
+            tokens.push(token);
+        }
+    }
+
+    #[test]
+    fn test_string_escapes() {
+        let src = r#""tab\tquote\" \\ \u{e9}\u{1F600}" r"C:\dir\n""#;
+        let tokens = scan(src).expect("scan failed");
+
+        let values: Vec<String> = tokens.iter().map(|t| t.string_value(src)).collect();
+        assert_eq!(values, ["tab\tquote\" \\ é😀", "C:\\dir\\n"]);
+    }
+
+    #[test]
+    fn test_multi_line_strings_count_lines() {
+        let src = "\"one\ntwo\" r\"three\nfour\" x";
+        let tokens = scan(src).expect("scan failed");
+
+        let lines: Vec<usize> = tokens.iter().map(|t| t.line).collect();
+        assert_eq!(lines, [1, 2, 3]);
+    }
+
+    #[test]
+    fn test_string_errors() {
+        let err = scan("\"ok\nthen \\q\"").expect_err("bad escape");
+        assert_eq!(err.desc, "Invalid escape sequence '\\q'.");
+        assert_eq!(err.line, 2);
+
+        let err = scan("\"\\u{110000}\"").expect_err("bad code point");
+        assert_eq!(err.desc, "Invalid unicode escape sequence.");
+
+        let err = scan("\nr\"open").expect_err("unterminated");
+        assert_eq!(err.desc, "Unterminated string.");
+        assert_eq!(err.line, 2);
+    }
+
+    #[test]
+    fn test_string_interpolation() {
+        let src = r#""a ${ {b} } c ${"d"}\${e}""#;
+        let tokens = scan(src).expect("scan failed");
+
+        let kinds: Vec<TokenKind> = tokens.iter().map(|t| t.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                TokenKind::Interpolation,
+                TokenKind::LeftBrace,
+                TokenKind::Identifier,
+                TokenKind::RightBrace,
+                TokenKind::Interpolation,
+                TokenKind::String,
+                TokenKind::String,
+            ]
+        );
+        assert_eq!(tokens[4].string_value(src), " c ");
+        assert_eq!(tokens[6].string_value(src), "${e}");
+
+        let err = scan("\"a ${b").expect_err("unterminated");
+        assert_eq!(err.desc, "Unterminated string.");
+    }
+
+    #[test]
+    fn test_number_literals() {
+        let cases = [
+            ("0xff", 255.0),
+            ("0B1010", 10.0),
+            ("1_000_000", 1e6),
+            ("1e3", 1000.0),
+            ("2.5E-1", 0.25),
+        ];
+        for (lexeme, value) in cases {
+            assert_eq!(parse_number(lexeme), Some(value), "{lexeme}");
+        }
+
+        for lexeme in ["1__0", "1_", "0x_1", "0x"] {
+            assert_eq!(parse_number(lexeme), None, "{lexeme}");
+        }
+        for src in ["0b102", "12abc", "1e", "0x_1"] {
+            let err = scan(src).expect_err(src);
+            assert_eq!(err.desc, "Invalid number literal.", "{src}");
+        }
+    }
+
+    #[test]
+    fn test_tilde_slash_and_comments() {
+        let tokens = scan("a ~/ b ~c // a comment\nif (x) // only when x\n").expect("scan failed");
+
+        let kinds: Vec<TokenKind> = tokens.iter().map(|t| t.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                TokenKind::Identifier,
+                TokenKind::TildeSlash,
+                TokenKind::Identifier,
+                TokenKind::Tilde,
+                TokenKind::Identifier,
+                TokenKind::If,
+                TokenKind::LeftParen,
+                TokenKind::Identifier,
+                TokenKind::RightParen,
+            ]
+        );
+    }
+}
@@ -1,8 +1,12 @@
-use std::fmt;
+use std::{collections::HashMap, fmt, rc::Rc};
+
+use crate::{chunk::Chunk, exec::VM};
 
 pub enum ArithmeticError {
     DivisionByZero,
     InvalidOperands,
+    NonIntegral,
+    InvalidShift,
 }
 
 type ArithResult<T = Value> = Result<T, ArithmeticError>;
@@ -13,6 +17,13 @@ pub enum ArithOp {
     Mul,
     Div,
     Mod,
+    IntDiv,
+    Pow,
+    BitAnd,
+    BitOr,
+    BitXor,
+    Shl,
+    Shr,
 }
 
 pub enum CompareOp {
@@ -21,11 +32,14 @@ pub enum CompareOp {
     Less,
 }
 
-#[derive(Clone, PartialEq, PartialOrd)]
+#[derive(Clone)]
 pub enum Constant {
     Number(f64),
     Boolean(bool),
-    String { start: usize, end: usize },
+    /// Names of globals and properties too, `Rc` so reading one at runtime doesn't allocate
+    String(Rc<str>),
+    /// Compiled function, each time it's loaded a new function object is allocated
+    Function(Rc<Function>),
     Nil,
 }
 
@@ -42,12 +56,147 @@ pub struct ObjRef(pub usize);
 
 pub enum Object {
     String(StrObj),
+    Native(NativeObj),
+    Array(ArrObj),
+    Function(Rc<Function>),
+    Fiber(FiberObj),
+    Namespace(NsObj),
+    Builtin(Builtin),
 }
 
 impl fmt::Display for Object {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Object::String(str) => write!(f, "{}", str.chars),
+            Object::Native(native) => write!(f, "<native fn {}>", native.name),
+            // The items live in the heap too, `fmt_value` is the one that can print them
+            Object::Array(arr) => write!(f, "<array {}>", arr.items.len()),
+            Object::Function(function) => write!(f, "{function}"),
+            Object::Fiber(_) => write!(f, "<fiber>"),
+            Object::Namespace(ns) => write!(f, "<namespace {}>", ns.name),
+            Object::Builtin(builtin) => write!(f, "<native fn {}>", builtin.name()),
+        }
+    }
+}
+
+/// Formats a value following its heap references, strings inside arrays are quoted
+pub fn fmt_value(value: Value, heap: &[Object]) -> String {
+    let mut out = String::new();
+    write_value(&mut out, value, heap, &mut Vec::new(), false);
+
+    out
+}
+
+/// `seen` holds the arrays being printed, an array that contains itself prints as `[...]`
+fn write_value(
+    out: &mut String,
+    value: Value,
+    heap: &[Object],
+    seen: &mut Vec<ObjRef>,
+    nested: bool,
+) {
+    let Value::Object(id) = value else {
+        out.push_str(&value.to_string());
+        return;
+    };
+
+    match &heap[id.0] {
+        Object::String(str) if nested => out.push_str(&format!("\"{}\"", str.chars)),
+        Object::Array(_) if seen.contains(&id) => out.push_str("[...]"),
+        Object::Array(arr) => {
+            seen.push(id);
+            out.push('[');
+            for (i, item) in arr.items.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                write_value(out, *item, heap, seen, true);
+            }
+            out.push(']');
+            seen.pop();
+        }
+        obj => out.push_str(&obj.to_string()),
+    }
+}
+
+/// Host function, gets the VM (to read or allocate strings) and the call arguments
+pub type NativeFn = fn(&mut VM, &[Value]) -> Result<Value, String>;
+
+pub struct NativeObj {
+    pub name: Box<str>,
+    pub arity: u8,
+    pub function: NativeFn,
+}
+
+pub struct ArrObj {
+    pub items: Vec<Value>,
+}
+
+pub struct Function {
+    pub name: Box<str>,
+    pub arity: u8,
+    pub chunk: Chunk,
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.name.is_empty() {
+            write!(f, "<script>")
+        } else {
+            write!(f, "<fn {}>", self.name)
+        }
+    }
+}
+
+/// A function being executed, `slots` is where its callee and arguments start in the stack
+pub struct CallFrame {
+    pub function: Rc<Function>,
+    pub ip: usize,
+    pub slots: usize,
+}
+
+/**
+ * A fiber owns a value stack and a frame stack. Only the running fiber's stacks are in the VM, the
+ * others keep theirs here until they are resumed. `caller` is the fiber that resumed this one with
+ * `call`, it gets control back on `Fiber.yield` or when the fiber's function returns.
+ */
+pub struct FiberObj {
+    pub stack: Vec<Value>,
+    pub frames: Vec<CallFrame>,
+    pub caller: Option<ObjRef>,
+    /// Not run yet, its function still waits for the argument of the first `call`
+    pub fresh: bool,
+    /// `Builtin::FiberCall` bound to this fiber, allocated the first time `call` is read
+    pub call: Option<ObjRef>,
+}
+
+impl FiberObj {
+    pub fn is_done(&self) -> bool {
+        self.frames.is_empty() && !self.fresh
+    }
+}
+
+/// Globals that group other values, like `Fiber.new`
+pub struct NsObj {
+    pub name: Box<str>,
+    pub members: HashMap<Box<str>, Value>,
+}
+
+/// Built-in functions that switch the running fiber, so they can't be plain natives
+#[derive(Clone, Copy)]
+pub enum Builtin {
+    FiberNew,
+    FiberYield,
+    /// `fiber.call`, bound to the fiber it was read from
+    FiberCall(ObjRef),
+}
+
+impl Builtin {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Builtin::FiberNew => "new",
+            Builtin::FiberYield => "yield",
+            Builtin::FiberCall(_) => "call",
         }
     }
 }
@@ -71,7 +220,8 @@ impl fmt::Display for Value {
         match self {
             Self::Number(n) => write!(f, "{n}"),
             Self::Boolean(b) => write!(f, "{b}"),
-            Self::Object(_) => write!(f, "obj"),
+            // Only the heap knows what the object is, `fmt_value` prints it
+            Self::Object(obj_ref) => write!(f, "<obj {}>", obj_ref.0),
             Self::Nil => write!(f, "nil"),
         }
     }
@@ -101,6 +251,19 @@ impl Value {
                     }
                 }
                 ArithOp::Mod => Ok(Value::Number(a % b)),
+                ArithOp::IntDiv => {
+                    if b == 0.0 {
+                        Err(ArithmeticError::DivisionByZero)
+                    } else {
+                        Ok(Value::Number((a / b).floor()))
+                    }
+                }
+                ArithOp::Pow => Ok(Value::Number(a.powf(b))),
+                ArithOp::BitAnd => Ok(Value::Number((integer(a)? & integer(b)?) as f64)),
+                ArithOp::BitOr => Ok(Value::Number((integer(a)? | integer(b)?) as f64)),
+                ArithOp::BitXor => Ok(Value::Number((integer(a)? ^ integer(b)?) as f64)),
+                ArithOp::Shl => Ok(Value::Number((integer(a)? << shift(b)?) as f64)),
+                ArithOp::Shr => Ok(Value::Number((integer(a)? >> shift(b)?) as f64)),
             }
         } else {
             Err(ArithmeticError::InvalidOperands)
@@ -125,13 +288,49 @@ impl Value {
     }
 }
 
+/// Bitwise operators work on the `i64` value of integral numbers
+pub fn integer(n: f64) -> ArithResult<i64> {
+    // i64::MAX isn't exactly representable, the bound is the first power of two above it
+    if n.fract() != 0.0 || n.abs() >= 2f64.powi(63) {
+        return Err(ArithmeticError::NonIntegral);
+    }
+
+    Ok(n as i64)
+}
+
+fn shift(n: f64) -> ArithResult<u32> {
+    u32::try_from(integer(n)?)
+        .ok()
+        .filter(|shift| *shift < i64::BITS)
+        .ok_or(ArithmeticError::InvalidShift)
+}
+
 impl fmt::Display for Constant {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Self::Number(n) => write!(f, "{n}"),
             Self::Boolean(b) => write!(f, "{b}"),
-            Self::String { start: _s, end: _e } => write!(f, "str"),
+            Self::String(s) => write!(f, "{s}"),
+            Self::Function(function) => write!(f, "{function}"),
             Self::Nil => write!(f, "nil"),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_integer_bounds() {
+        let limit = 2f64.powi(63);
+
+        assert_eq!(integer(-7.0).ok(), Some(-7));
+        // Largest f64 below 2^63, the ones between are not representable
+        assert_eq!(integer(limit - 1024.0).ok(), Some(i64::MAX - 1023));
+
+        for n in [limit, -limit, 0.5, f64::NAN, f64::INFINITY] {
+            assert!(integer(n).is_err(), "{n}");
+        }
+    }
+}